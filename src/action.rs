@@ -0,0 +1,417 @@
+use crate::editor::ZoomFit;
+use crate::geometry::Scale;
+use crate::{hotkey, tools, HotkeyAction, KeyModifier, KeyWithMod};
+use sdl2::keyboard::Keycode;
+
+/// A named command which can be bound to keys and run from the command palette.
+pub struct Action {
+    /// The identifier used in the keybindings file.
+    pub name: String,
+    /// A human-readable description shown in the command palette.
+    pub title: String,
+    pub callbacks: HotkeyAction,
+}
+
+impl Action {
+    pub fn new(name: &str, title: &str, callbacks: HotkeyAction) -> Action {
+        Action {
+            name: name.to_owned(),
+            title: title.to_owned(),
+            callbacks,
+        }
+    }
+
+    /// Whether the action only makes sense while a key is held, e.g. scrolling with Space.
+    pub fn is_held(&self) -> bool {
+        self.callbacks.on_release.is_some()
+    }
+}
+
+pub fn find(name: &str) -> Option<&'static Action> {
+    ACTIONS.iter().find(|action| action.name == name)
+}
+
+pub fn tool_action_name(tool_name: &str) -> String {
+    format!("tool-{}", tool_name.to_lowercase())
+}
+
+lazy_static! {
+    pub static ref ACTIONS: Vec<Action> = {
+        let mut actions = vec![
+            Action::new(
+                "undo",
+                "Undo",
+                HotkeyAction::new(Some(Box::new(hotkey::handle_undo)), None),
+            ),
+            Action::new(
+                "redo",
+                "Redo",
+                HotkeyAction::new(Some(Box::new(hotkey::handle_redo)), None),
+            ),
+            Action::new(
+                "scroll",
+                "Scroll (hold)",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| oxi.start_scrolling())),
+                    Some(Box::new(|oxi| oxi.stop_scrolling())),
+                ),
+            ),
+            Action::new(
+                "save",
+                "Save",
+                HotkeyAction::new(Some(hotkey::catch(Box::new(hotkey::save))), None),
+            ),
+            Action::new(
+                "open",
+                "Open",
+                HotkeyAction::new(Some(hotkey::catch(Box::new(hotkey::open))), None),
+            ),
+            Action::new(
+                "brush-size-down",
+                "Decrease brush size",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| hotkey::change_brush_size(oxi, -1))),
+                    None,
+                ),
+            ),
+            Action::new(
+                "brush-size-up",
+                "Increase brush size",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| hotkey::change_brush_size(oxi, 1))),
+                    None,
+                ),
+            ),
+            Action::new(
+                "zoom-to-fit",
+                "Zoom to fit",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| hotkey::zoom_to_fit(oxi, ZoomFit::Fit))),
+                    None,
+                ),
+            ),
+            Action::new(
+                "zoom-to-fill",
+                "Zoom to fill",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| hotkey::zoom_to_fit(oxi, ZoomFit::Fill))),
+                    None,
+                ),
+            ),
+            Action::new(
+                "zoom-in",
+                "Zoom in",
+                HotkeyAction::new(Some(Box::new(|oxi| hotkey::zoom_step(oxi, true))), None),
+            ),
+            Action::new(
+                "zoom-out",
+                "Zoom out",
+                HotkeyAction::new(Some(Box::new(|oxi| hotkey::zoom_step(oxi, false))), None),
+            ),
+            Action::new(
+                "zoom-actual-size",
+                "Zoom to actual size",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| hotkey::zoom_to(oxi, Scale::Times(1)))),
+                    None,
+                ),
+            ),
+            Action::new(
+                "ask-zoom",
+                "Set zoom percentage",
+                HotkeyAction::new(Some(Box::new(hotkey::ask_zoom)), None),
+            ),
+            Action::new(
+                "toggle-pixel-grid",
+                "Toggle pixel grid",
+                HotkeyAction::new(Some(Box::new(hotkey::toggle_pixel_grid)), None),
+            ),
+            Action::new(
+                "toggle-tile-grid",
+                "Toggle tile grid",
+                HotkeyAction::new(Some(Box::new(hotkey::toggle_tile_grid)), None),
+            ),
+            Action::new(
+                "cycle-tile-size",
+                "Cycle tile grid size",
+                HotkeyAction::new(Some(Box::new(hotkey::cycle_tile_size)), None),
+            ),
+            Action::new(
+                "copy",
+                "Copy",
+                HotkeyAction::new(Some(Box::new(hotkey::copy)), None),
+            ),
+            Action::new(
+                "cut",
+                "Cut",
+                HotkeyAction::new(Some(Box::new(hotkey::cut)), None),
+            ),
+            Action::new(
+                "paste",
+                "Paste",
+                HotkeyAction::new(Some(Box::new(hotkey::paste)), None),
+            ),
+            Action::new(
+                "deselect",
+                "Deselect",
+                HotkeyAction::new(Some(Box::new(hotkey::deselect)), None),
+            ),
+            Action::new(
+                "eraser-size-down",
+                "Decrease eraser size",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| hotkey::change_eraser_size(oxi, -1))),
+                    None,
+                ),
+            ),
+            Action::new(
+                "eraser-size-up",
+                "Increase eraser size",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| hotkey::change_eraser_size(oxi, 1))),
+                    None,
+                ),
+            ),
+            Action::new(
+                "previous-branch",
+                "Switch to the previous history branch",
+                HotkeyAction::new(Some(Box::new(|oxi| hotkey::switch_branch(oxi, -1))), None),
+            ),
+            Action::new(
+                "next-branch",
+                "Switch to the next history branch",
+                HotkeyAction::new(Some(Box::new(|oxi| hotkey::switch_branch(oxi, 1))), None),
+            ),
+            Action::new(
+                "step-back-in-time",
+                "Step back in time",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| hotkey::step_chronologically(oxi, -1))),
+                    None,
+                ),
+            ),
+            Action::new(
+                "step-forward-in-time",
+                "Step forward in time",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| hotkey::step_chronologically(oxi, 1))),
+                    None,
+                ),
+            ),
+            Action::new(
+                "toggle-history-panel",
+                "Toggle history panel",
+                HotkeyAction::new(Some(Box::new(hotkey::toggle_history_panel)), None),
+            ),
+            Action::new(
+                "add-checkpoint",
+                "Add checkpoint",
+                HotkeyAction::new(Some(Box::new(hotkey::add_checkpoint)), None),
+            ),
+            Action::new(
+                "revert-to-checkpoint",
+                "Revert to a checkpoint",
+                HotkeyAction::new(Some(Box::new(hotkey::revert_to_checkpoint)), None),
+            ),
+            Action::new(
+                "swap-colors",
+                "Swap colors",
+                HotkeyAction::new(Some(Box::new(hotkey::swap_colors)), None),
+            ),
+            Action::new(
+                "cycle-picker-size",
+                "Cycle picker size",
+                HotkeyAction::new(Some(Box::new(hotkey::cycle_picker_size)), None),
+            ),
+            Action::new(
+                "toggle-shape-filled",
+                "Toggle filled shapes",
+                HotkeyAction::new(Some(Box::new(hotkey::toggle_shape_filled)), None),
+            ),
+            Action::new(
+                "fill-tolerance-down",
+                "Decrease fill tolerance",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| hotkey::change_fill_tolerance(oxi, -8))),
+                    None,
+                ),
+            ),
+            Action::new(
+                "fill-tolerance-up",
+                "Increase fill tolerance",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| hotkey::change_fill_tolerance(oxi, 8))),
+                    None,
+                ),
+            ),
+            Action::new(
+                "toggle-fill-connectivity",
+                "Toggle fill connectivity",
+                HotkeyAction::new(Some(Box::new(hotkey::toggle_fill_connectivity)), None),
+            ),
+            Action::new(
+                "toggle-fill-global",
+                "Toggle global fill",
+                HotkeyAction::new(Some(Box::new(hotkey::toggle_fill_global)), None),
+            ),
+            Action::new(
+                "add-layer",
+                "Add layer",
+                HotkeyAction::new(Some(Box::new(hotkey::add_layer)), None),
+            ),
+            Action::new(
+                "remove-layer",
+                "Remove layer",
+                HotkeyAction::new(Some(Box::new(hotkey::remove_layer)), None),
+            ),
+            Action::new(
+                "select-layer-above",
+                "Select the layer above",
+                HotkeyAction::new(Some(Box::new(|oxi| hotkey::select_layer(oxi, 1))), None),
+            ),
+            Action::new(
+                "select-layer-below",
+                "Select the layer below",
+                HotkeyAction::new(Some(Box::new(|oxi| hotkey::select_layer(oxi, -1))), None),
+            ),
+            Action::new(
+                "move-layer-up",
+                "Move layer up",
+                HotkeyAction::new(Some(Box::new(|oxi| hotkey::move_layer(oxi, 1))), None),
+            ),
+            Action::new(
+                "move-layer-down",
+                "Move layer down",
+                HotkeyAction::new(Some(Box::new(|oxi| hotkey::move_layer(oxi, -1))), None),
+            ),
+            Action::new(
+                "toggle-layer-visibility",
+                "Toggle layer visibility",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| {
+                        hotkey::update_active_layer(oxi, |layer| layer.visible = !layer.visible)
+                    })),
+                    None,
+                ),
+            ),
+            Action::new(
+                "toggle-layer-lock",
+                "Toggle layer lock",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| {
+                        hotkey::update_active_layer(oxi, |layer| layer.locked = !layer.locked)
+                    })),
+                    None,
+                ),
+            ),
+            Action::new(
+                "layer-opacity-down",
+                "Decrease layer opacity",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| {
+                        hotkey::update_active_layer(oxi, |layer| {
+                            layer.opacity = (layer.opacity - 0.1).max(0.0)
+                        })
+                    })),
+                    None,
+                ),
+            ),
+            Action::new(
+                "layer-opacity-up",
+                "Increase layer opacity",
+                HotkeyAction::new(
+                    Some(Box::new(|oxi| {
+                        hotkey::update_active_layer(oxi, |layer| {
+                            layer.opacity = (layer.opacity + 0.1).min(1.0)
+                        })
+                    })),
+                    None,
+                ),
+            ),
+            Action::new(
+                "command-palette",
+                "Show all commands",
+                HotkeyAction::new(Some(Box::new(|oxi| oxi.open_command_palette())), None),
+            ),
+        ];
+        actions.extend(tools::list().iter().enumerate().map(|(index, tool)| {
+            Action {
+                name: tool_action_name(&tool.name()),
+                title: format!("{} tool", tool.name()),
+                callbacks: HotkeyAction::new(
+                    Some(Box::new(move |oxi| oxi.select_tool(index))),
+                    None,
+                ),
+            }
+        }));
+        actions
+    };
+
+    /// The default keybindings, referring to actions by name.
+    pub static ref HOTKEYS: Vec<(KeyWithMod, String)> = {
+        let hotkeys = vec![
+            (KeyModifier::new().ctrl().key(Keycode::Z), "undo"),
+            (KeyModifier::new().ctrl().key(Keycode::Y), "redo"),
+            (KeyModifier::new().key(Keycode::Space), "scroll"),
+            (KeyModifier::new().ctrl().key(Keycode::S), "save"),
+            (KeyModifier::new().ctrl().key(Keycode::O), "open"),
+            (KeyModifier::new().key(Keycode::LeftBracket), "brush-size-down"),
+            (KeyModifier::new().key(Keycode::RightBracket), "brush-size-up"),
+            (KeyModifier::new().ctrl().key(Keycode::Num0), "zoom-to-fit"),
+            (KeyModifier::new().ctrl().key(Keycode::Num9), "zoom-to-fill"),
+            (KeyModifier::new().ctrl().key(Keycode::Num1), "zoom-actual-size"),
+            (KeyModifier::new().key(Keycode::Equals), "zoom-in"),
+            (KeyModifier::new().key(Keycode::KpPlus), "zoom-in"),
+            (KeyModifier::new().key(Keycode::Minus), "zoom-out"),
+            (KeyModifier::new().key(Keycode::KpMinus), "zoom-out"),
+            (KeyModifier::new().ctrl().key(Keycode::Slash), "ask-zoom"),
+            (KeyModifier::new().ctrl().key(Keycode::Quote), "toggle-pixel-grid"),
+            (KeyModifier::new().ctrl().key(Keycode::Semicolon), "toggle-tile-grid"),
+            (KeyModifier::new().ctrl().key(Keycode::Backslash), "cycle-tile-size"),
+            (KeyModifier::new().ctrl().key(Keycode::C), "copy"),
+            (KeyModifier::new().ctrl().key(Keycode::X), "cut"),
+            (KeyModifier::new().ctrl().key(Keycode::V), "paste"),
+            (KeyModifier::new().ctrl().key(Keycode::D), "deselect"),
+            (KeyModifier::new().ctrl().key(Keycode::LeftBracket), "eraser-size-down"),
+            (KeyModifier::new().ctrl().key(Keycode::RightBracket), "eraser-size-up"),
+            (KeyModifier::new().ctrl().key(Keycode::Left), "previous-branch"),
+            (KeyModifier::new().ctrl().key(Keycode::Right), "next-branch"),
+            (KeyModifier::new().ctrl().key(Keycode::Minus), "step-back-in-time"),
+            (KeyModifier::new().ctrl().key(Keycode::Equals), "step-forward-in-time"),
+            (KeyModifier::new().key(Keycode::F2), "toggle-history-panel"),
+            (KeyModifier::new().ctrl().key(Keycode::K), "add-checkpoint"),
+            (KeyModifier::new().ctrl().key(Keycode::J), "revert-to-checkpoint"),
+            (KeyModifier::new().key(Keycode::X), "swap-colors"),
+            (KeyModifier::new().ctrl().key(Keycode::I), "cycle-picker-size"),
+            (KeyModifier::new().ctrl().key(Keycode::F), "toggle-shape-filled"),
+            (KeyModifier::new().key(Keycode::Comma), "fill-tolerance-down"),
+            (KeyModifier::new().key(Keycode::Period), "fill-tolerance-up"),
+            (KeyModifier::new().ctrl().key(Keycode::Num8), "toggle-fill-connectivity"),
+            (KeyModifier::new().ctrl().key(Keycode::G), "toggle-fill-global"),
+            (KeyModifier::new().ctrl().key(Keycode::Insert), "add-layer"),
+            (KeyModifier::new().ctrl().key(Keycode::Delete), "remove-layer"),
+            (KeyModifier::new().key(Keycode::PageUp), "select-layer-above"),
+            (KeyModifier::new().key(Keycode::PageDown), "select-layer-below"),
+            (KeyModifier::new().ctrl().key(Keycode::PageUp), "move-layer-up"),
+            (KeyModifier::new().ctrl().key(Keycode::PageDown), "move-layer-down"),
+            (KeyModifier::new().ctrl().key(Keycode::H), "toggle-layer-visibility"),
+            (KeyModifier::new().ctrl().key(Keycode::L), "toggle-layer-lock"),
+            (KeyModifier::new().ctrl().key(Keycode::Comma), "layer-opacity-down"),
+            (KeyModifier::new().ctrl().key(Keycode::Period), "layer-opacity-up"),
+            (
+                KeyModifier::new().ctrl().shift().key(Keycode::P),
+                "command-palette",
+            ),
+        ];
+        let mut hotkeys: Vec<_> = hotkeys
+            .into_iter()
+            .map(|(key, name)| (key, name.to_owned()))
+            .collect();
+        hotkeys.extend(
+            tools::list()
+                .iter()
+                .map(|tool| (tool.default_shortcut(), tool_action_name(&tool.name()))),
+        );
+        hotkeys
+    };
+}
@@ -0,0 +1,140 @@
+use crate::image_io::{self, Format, RgbaImage};
+use crate::project;
+use std::error::Error;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Operation {
+    /// Scales the image to the given size with nearest neighbour sampling, which keeps pixel
+    /// art crisp.
+    Resize { width: u32, height: u32 },
+    /// Mirrors the image, swapping either its left and right or its top and bottom.
+    Flip(Axis),
+}
+
+impl Operation {
+    pub fn apply(self, image: &RgbaImage) -> RgbaImage {
+        match self {
+            Operation::Resize { width, height } => resize(image, width, height),
+            Operation::Flip(axis) => flip(image, axis),
+        }
+    }
+}
+
+/// A headless conversion of one file, which never touches SDL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub format: Format,
+    pub plain: bool,
+    pub operations: Vec<Operation>,
+}
+
+impl Job {
+    pub fn run(&self) -> Result<(), Box<dyn Error>> {
+        // Projects are flattened, since none of the image formats can hold layers. Their
+        // history is of no use here, so it is not even read.
+        let mut image = if project::is_project_path(&self.input) {
+            project::load_without_history(&self.input)?.flatten()?
+        } else {
+            image_io::load(&self.input)?
+        };
+        for operation in &self.operations {
+            image = operation.apply(&image);
+        }
+        image_io::save(&self.output, &image, self.format, self.plain)
+    }
+}
+
+fn resize(image: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let mut data = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height as u64 {
+        let src_y = y * image.height as u64 / height as u64;
+        for x in 0..width as u64 {
+            let src_x = x * image.width as u64 / width as u64;
+            let offset = (src_y as usize * image.width as usize + src_x as usize) * 4;
+            data.extend_from_slice(&image.data[offset..offset + 4]);
+        }
+    }
+    RgbaImage {
+        width,
+        height,
+        data,
+    }
+}
+
+fn flip(image: &RgbaImage, axis: Axis) -> RgbaImage {
+    let rows = image.data.chunks_exact(image.width as usize * 4);
+    let data = match axis {
+        Axis::Horizontal => rows
+            .flat_map(|row| row.chunks_exact(4).rev().flatten())
+            .copied()
+            .collect(),
+        Axis::Vertical => rows.rev().flatten().copied().collect(),
+    };
+    RgbaImage {
+        width: image.width,
+        height: image.height,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x2 image whose pixels are numbered row by row.
+    fn image() -> RgbaImage {
+        RgbaImage {
+            width: 3,
+            height: 2,
+            data: (0..6).flat_map(|i| vec![i, i, i, 255]).collect(),
+        }
+    }
+
+    fn pixels(image: &RgbaImage) -> Vec<u8> {
+        image.data.chunks(4).map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn flip() {
+        let horizontal = Operation::Flip(Axis::Horizontal).apply(&image());
+        assert_eq!((horizontal.width, horizontal.height), (3, 2));
+        assert_eq!(pixels(&horizontal), vec![2, 1, 0, 5, 4, 3]);
+        let vertical = Operation::Flip(Axis::Vertical).apply(&image());
+        assert_eq!(pixels(&vertical), vec![3, 4, 5, 0, 1, 2]);
+    }
+
+    #[test]
+    fn resize() {
+        let larger = Operation::Resize {
+            width: 6,
+            height: 4,
+        }
+        .apply(&image());
+        assert_eq!((larger.width, larger.height), (6, 4));
+        assert_eq!(
+            pixels(&larger),
+            vec![
+                0, 0, 1, 1, 2, 2, //
+                0, 0, 1, 1, 2, 2, //
+                3, 3, 4, 4, 5, 5, //
+                3, 3, 4, 4, 5, 5,
+            ]
+        );
+        let smaller = Operation::Resize {
+            width: 2,
+            height: 1,
+        }
+        .apply(&image());
+        assert_eq!(pixels(&smaller), vec![0, 1]);
+        assert!(smaller.data.chunks(4).all(|pixel| pixel[3] == 255));
+    }
+}
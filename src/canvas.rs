@@ -1,19 +1,47 @@
+use crate::dirty::DirtyTiles;
 use crate::geometry::{Point, Scale};
-use crate::history::{Diff, DiffDirection, SparsePixelDelta};
+use crate::history::{Diff, DiffDirection};
+use crate::image_io::RgbaImage;
+use crate::layer::{composite_row, Layer, LayerId};
+use crate::snapshot::Snapshot;
 use crate::SdlCanvas;
 use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
-use sdl2::render::{Texture, TextureCreator};
+use sdl2::render::{BlendMode, Texture, TextureCreator};
 use sdl2::video::WindowContext;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 pub struct Canvas {
-    data: Vec<u8>,
+    layers: Vec<Layer>,
+    active_layer: usize,
+    next_layer_id: u32,
+    composite: Vec<u8>,
+    /// Runs of preview pixels, by row.
+    preview: HashMap<u32, Vec<PreviewSpan>>,
     width: u32,
     height: u32,
     sdl_canvas: Rc<RefCell<SdlCanvas>>,
+    texture_creator: &'static TextureCreator<WindowContext>,
     texture: Texture<'static>,
+    downsampled_texture: Option<(Scale, Texture<'static>)>,
+    snapshot: Option<Snapshot>,
+    dirty: DirtyTiles,
+    downsampled_dirty: DirtyTiles,
+}
+
+/// A run of preview pixels within a row, from `left` up to, but not including, `right`.
+struct PreviewSpan {
+    left: u32,
+    right: u32,
+    pixels: PreviewPixels,
+}
+
+enum PreviewPixels {
+    Solid(Color),
+    /// One color per pixel of the span.
+    Each(Vec<Color>),
 }
 
 impl Canvas {
@@ -31,27 +59,151 @@ impl Canvas {
         let texture = Self::create_sdl_texture(texture_creator, width, height);
 
         Canvas {
+            layers: vec![Layer::new(LayerId(0), "Background".to_owned(), data)],
+            active_layer: 0,
+            next_layer_id: 1,
+            composite: vec![0; data_size],
+            preview: HashMap::new(),
             width,
             height,
-            data,
             sdl_canvas,
+            texture_creator,
             texture,
+            downsampled_texture: None,
+            snapshot: None,
+            dirty: DirtyTiles::new(width, height),
+            downsampled_dirty: DirtyTiles::new(width, height),
+        }
+    }
+
+    /// Replaces the whole canvas with the given image, resizing it (and the texture) as needed.
+    /// All existing layers are dropped, and the image becomes the only one.
+    pub fn load_image(&mut self, image: &RgbaImage) {
+        assert_eq!(
+            image.data.len(),
+            image.width as usize * image.height as usize * Self::BPP
+        );
+        let mut data = image.data.clone();
+        for slice in data.chunks_mut(4) {
+            slice.swap(0, 2);
         }
+        let layer = Layer::new(LayerId(0), "Background".to_owned(), data);
+        self.load_layers(image.width, image.height, vec![layer], 0);
     }
 
-    pub fn build_image(&self) -> Vec<u8> {
-        let mut buf = self.data.clone();
+    /// Replaces the whole canvas with the given layers, resizing it (and the texture) as
+    /// needed.
+    pub fn load_layers(
+        &mut self,
+        width: u32,
+        height: u32,
+        layers: Vec<Layer>,
+        active_layer: usize,
+    ) {
+        assert!(active_layer < layers.len());
+        self.width = width;
+        self.height = height;
+        for layer in &layers {
+            assert_eq!(layer.data.len(), self.area() * Self::BPP);
+        }
+        self.next_layer_id = layers.iter().map(|layer| layer.id.0 + 1).max().unwrap();
+        self.layers = layers;
+        self.active_layer = active_layer;
+        self.composite = vec![0; self.area() * Self::BPP];
+        self.preview.clear();
+        self.texture = Self::create_sdl_texture(self.texture_creator, width, height);
+        self.downsampled_texture = None;
+        self.snapshot = None;
+        self.dirty = DirtyTiles::new(width, height);
+        self.downsampled_dirty = DirtyTiles::new(width, height);
+    }
+
+    pub fn build_image(&mut self) -> Vec<u8> {
+        self.composite_rect(Rect::new(0, 0, self.width, self.height));
+        let mut buf = self.composite.clone();
         for slice in buf.chunks_mut(4) {
             slice.swap(0, 2);
         }
         buf
     }
 
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Gives access to a layer, assuming that its visible properties are going to change.
+    pub fn layer_mut(&mut self, index: usize) -> Option<&mut Layer> {
+        self.mark_all_dirty();
+        self.layers.get_mut(index)
+    }
+
+    pub fn active_layer_index(&self) -> usize {
+        self.active_layer
+    }
+
+    pub fn active_layer(&self) -> &Layer {
+        &self.layers[self.active_layer]
+    }
+
+    pub fn set_active_layer(&mut self, index: usize) -> Option<()> {
+        if index < self.layers.len() {
+            self.active_layer = index;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Creates a new fully transparent layer without adding it to the layer stack.
+    pub fn create_layer(&mut self) -> Layer {
+        let id = LayerId(self.next_layer_id);
+        self.next_layer_id += 1;
+        let data = vec![0; self.area() * Self::BPP];
+        Layer::new(id, format!("Layer {}", id.0), data)
+    }
+
+    pub fn insert_layer(&mut self, index: usize, layer: Layer) {
+        assert_eq!(layer.data.len(), self.area() * Self::BPP);
+        self.next_layer_id = self.next_layer_id.max(layer.id.0 + 1);
+        self.layers.insert(index, layer);
+        self.active_layer = index;
+        self.mark_all_dirty();
+    }
+
+    pub fn remove_layer(&mut self, index: usize) -> Layer {
+        assert!(self.layers.len() > 1, "Cannot remove the last layer");
+        let layer = self.layers.remove(index);
+        if self.active_layer >= self.layers.len() || self.active_layer > index {
+            self.active_layer -= 1;
+        }
+        self.mark_all_dirty();
+        layer
+    }
+
+    pub fn move_layer(&mut self, from: usize, to: usize) {
+        let layer = self.layers.remove(from);
+        self.layers.insert(to, layer);
+        self.mark_all_dirty();
+        let active = self.active_layer;
+        self.active_layer = if active == from {
+            to
+        } else if from < active && active <= to {
+            active - 1
+        } else if to <= active && active < from {
+            active + 1
+        } else {
+            active
+        };
+    }
+
+    fn find_layer_mut(&mut self, id: LayerId) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.id == id)
+    }
+
     pub fn area(&self) -> usize {
         self.width as usize * self.height as usize
     }
 
-    #[allow(dead_code)]
     pub fn get_at(&self, x: u32, y: u32) -> Color {
         self.try_get_at(x, y).unwrap()
     }
@@ -59,7 +211,7 @@ impl Canvas {
     pub fn try_get_at(&self, x: u32, y: u32) -> Option<Color> {
         // TODO: avoid multiple bound checking
         let offset = self.calc_offset(x, y)?;
-        let slice = &self.data[offset..offset + Self::BPP];
+        let slice = &self.active_layer().data[offset..offset + Self::BPP];
         Some(Self::color_from_slice(slice))
     }
 
@@ -80,19 +232,111 @@ impl Canvas {
         slice[3] = color.a;
     }
 
-    #[allow(dead_code)]
     pub fn set_at(&mut self, x: u32, y: u32, color: Color) {
         self.try_set_at(x, y, color).unwrap();
     }
 
+    /// Sets a pixel on the active layer. Writes to a locked layer are silently ignored.
     pub fn try_set_at(&mut self, x: u32, y: u32, color: Color) -> Option<()> {
         // TODO: avoid multiple bound checking
         let offset = self.calc_offset(x, y)?;
-        let slice = &mut self.data[offset..offset + Self::BPP];
+        let layer = &mut self.layers[self.active_layer];
+        if layer.locked {
+            return Some(());
+        }
+        if let Some(snapshot) = &mut self.snapshot {
+            if snapshot.layer() == layer.id {
+                snapshot.save_tile_at(x, y, &layer.data);
+            }
+        }
+        let slice = &mut layer.data[offset..offset + Self::BPP];
         Self::color_to_slice(color, slice);
+        self.dirty.mark_at(x, y);
+        self.downsampled_dirty.mark_at(x, y);
         Some(())
     }
 
+    fn mark_dirty(&mut self, rect: Rect) {
+        self.dirty.mark_rect(rect);
+        self.downsampled_dirty.mark_rect(rect);
+    }
+
+    fn mark_all_dirty(&mut self) {
+        self.dirty.mark_all();
+        self.downsampled_dirty.mark_all();
+    }
+
+    fn mark_preview_dirty(&mut self) {
+        for (&y, spans) in self.preview.iter() {
+            for span in spans {
+                let rect = Rect::new(span.left as i32, y as i32, span.right - span.left, 1);
+                self.dirty.mark_rect(rect);
+                self.downsampled_dirty.mark_rect(rect);
+            }
+        }
+    }
+
+    /// Shows the given pixels on top of the active layer without modifying it. The preview is
+    /// only visible on screen and is replaced by subsequent calls.
+    pub fn set_preview(&mut self, pixels: impl IntoIterator<Item = (u32, u32, Color)>) {
+        self.clear_preview();
+        for (x, y, color) in pixels {
+            if x >= self.width || y >= self.height {
+                continue;
+            }
+            let spans = self.preview.entry(y).or_default();
+            match spans.last_mut() {
+                Some(PreviewSpan {
+                    right,
+                    pixels: PreviewPixels::Each(colors),
+                    ..
+                }) if *right == x => {
+                    colors.push(color);
+                    *right += 1;
+                }
+                _ => spans.push(PreviewSpan {
+                    left: x,
+                    right: x + 1,
+                    pixels: PreviewPixels::Each(vec![color]),
+                }),
+            }
+        }
+        self.mark_preview_dirty();
+    }
+
+    /// Shows runs of a single color on top of the active layer, like `set_preview`. Each span
+    /// is a row and the pixels from its left end up to, but not including, its right end.
+    pub fn set_preview_spans(
+        &mut self,
+        color: Color,
+        spans: impl IntoIterator<Item = (u32, u32, u32)>,
+    ) {
+        self.clear_preview();
+        for (y, left, right) in spans {
+            let right = right.min(self.width);
+            if y >= self.height || left >= right {
+                continue;
+            }
+            let row = self.preview.entry(y).or_default();
+            match row.last_mut() {
+                Some(last) if last.left <= left && left <= last.right => {
+                    last.right = last.right.max(right)
+                }
+                _ => row.push(PreviewSpan {
+                    left,
+                    right,
+                    pixels: PreviewPixels::Solid(color),
+                }),
+            }
+        }
+        self.mark_preview_dirty();
+    }
+
+    pub fn clear_preview(&mut self) {
+        self.mark_preview_dirty();
+        self.preview.clear();
+    }
+
     pub fn width(&self) -> u32 {
         self.width
     }
@@ -106,63 +350,135 @@ impl Canvas {
     }
 
     pub fn draw(&mut self, scale: Scale, visible_rect: Rect, left_top_offset: Point<i32>) {
-        self.update_sdl_texture(visible_rect);
-        let texture = &self.texture;
-        let query = texture.query();
-        let mut texture_scaled_rect =
-            Rect::new(0, 0, scale.apply(query.width), scale.apply(query.height));
+        let texture = if let Scale::Fraction {
+            numerator,
+            denominator,
+        } = scale
+        {
+            self.update_downsampled_texture(numerator, denominator, visible_rect);
+            &self.downsampled_texture.as_ref().unwrap().1
+        } else {
+            self.update_sdl_texture(visible_rect);
+            &self.texture
+        };
+        let mut texture_scaled_rect = Rect::new(
+            0,
+            0,
+            scale.apply(self.width).max(1),
+            scale.apply(self.height).max(1),
+        );
         texture_scaled_rect.reposition((left_top_offset.x, left_top_offset.y));
+        self.draw_checkerboard(texture_scaled_rect);
         self.sdl_canvas
             .borrow_mut()
             .copy(texture, None, Some(texture_scaled_rect))
             .expect("Failed to draw texture");
     }
 
-    pub fn create_shadow_data(&self) -> Vec<u8> {
-        self.data.clone()
+    /// Draws a checkerboard pattern behind the canvas, so that transparent areas can be told
+    /// apart from opaque ones.
+    fn draw_checkerboard(&self, canvas_rect: Rect) {
+        let mut sdl_canvas = self.sdl_canvas.borrow_mut();
+        let (screen_width, screen_height) = sdl_canvas.window().drawable_size();
+        let area = match canvas_rect.intersection(Rect::new(0, 0, screen_width, screen_height)) {
+            Some(area) => area,
+            None => return,
+        };
+
+        let cell = Self::CHECKERBOARD_CELL_SIZE as i32;
+        let first_column = (area.left() - canvas_rect.left()) / cell;
+        let first_row = (area.top() - canvas_rect.top()) / cell;
+        let last_column = (area.right() - 1 - canvas_rect.left()) / cell;
+        let last_row = (area.bottom() - 1 - canvas_rect.top()) / cell;
+
+        let mut dark_cells = Vec::new();
+        for row in first_row..=last_row {
+            for column in first_column..=last_column {
+                if (row + column) % 2 == 1 {
+                    let cell_rect = Rect::new(
+                        canvas_rect.left() + column * cell,
+                        canvas_rect.top() + row * cell,
+                        cell as u32,
+                        cell as u32,
+                    );
+                    if let Some(cell_rect) = cell_rect.intersection(area) {
+                        dark_cells.push(cell_rect);
+                    }
+                }
+            }
+        }
+
+        sdl_canvas.set_draw_color(Color::RGB(204, 204, 204));
+        sdl_canvas
+            .fill_rect(area)
+            .expect("Failed to draw the checkerboard");
+        sdl_canvas.set_draw_color(Color::RGB(153, 153, 153));
+        sdl_canvas
+            .fill_rects(&dark_cells)
+            .expect("Failed to draw the checkerboard");
     }
 
-    pub fn update_shadow_data(&self, shadow_data: &mut Vec<u8>) {
-        shadow_data.clear();
-        shadow_data.extend_from_slice(&self.data);
+    /// Starts tracking changes to the active layer.
+    pub fn begin_snapshot(&mut self) {
+        self.snapshot = Some(Snapshot::new(
+            self.active_layer().id,
+            self.width,
+            self.height,
+        ));
     }
 
-    pub fn compare_shadow_data(&self, shadow_data: &Vec<u8>) -> Diff {
-        let mut deltas = Vec::new();
-        for index in 0..self.area() {
-            let left = index * Self::BPP;
-            let right = left + Self::BPP;
-            let before = Self::color_from_slice(&shadow_data[left..right]);
-            let after = Self::color_from_slice(&self.data[left..right]);
-            if before != after {
-                deltas.push(SparsePixelDelta {
-                    index,
-                    before,
-                    after,
-                });
-            }
+    /// Stops tracking changes and returns them. Returns `None` if nothing has changed.
+    pub fn finish_snapshot(&mut self) -> Option<Diff> {
+        let snapshot = self.snapshot.take()?;
+        let layer = snapshot.layer();
+        let data = &self
+            .layers
+            .iter()
+            .find(|candidate| candidate.id == layer)?
+            .data;
+        let tiles = snapshot.into_patches(data);
+        if tiles.is_empty() {
+            None
+        } else {
+            Some(Diff::Tiled { layer, tiles })
         }
-        Diff::Sparse(deltas)
     }
 
     pub fn apply_diff(&mut self, diff: &Diff, direction: DiffDirection) {
         match diff {
-            Diff::Sparse(deltas) => {
-                for delta in deltas.iter() {
-                    let left = delta.index * Self::BPP;
-                    let right = left + Self::BPP;
-                    let slice = &mut self.data[left..right];
-                    let color = match direction {
-                        DiffDirection::Normal => delta.after,
-                        DiffDirection::Reverse => delta.before,
-                    };
-
-                    Self::color_to_slice(color, slice);
+            Diff::Tiled { layer, tiles } => {
+                let width = self.width();
+                let layer = self
+                    .find_layer_mut(*layer)
+                    .expect("History refers to a non-existent layer");
+                // Patches are symmetric, so the direction does not matter.
+                for patch in tiles {
+                    patch.apply(&mut layer.data, width);
+                }
+                for patch in tiles {
+                    self.mark_dirty(patch.rect());
                 }
             }
+            Diff::InsertLayer { index, layer } => match direction {
+                DiffDirection::Normal => self.insert_layer(*index, layer.as_ref().clone()),
+                DiffDirection::Reverse => {
+                    self.remove_layer(*index);
+                }
+            },
+            Diff::RemoveLayer { index, layer } => match direction {
+                DiffDirection::Normal => {
+                    self.remove_layer(*index);
+                }
+                DiffDirection::Reverse => self.insert_layer(*index, layer.as_ref().clone()),
+            },
+            Diff::MoveLayer { from, to } => match direction {
+                DiffDirection::Normal => self.move_layer(*from, *to),
+                DiffDirection::Reverse => self.move_layer(*to, *from),
+            },
         }
     }
 
+    #[allow(dead_code)]
     pub fn contains_point(&self, point: Point) -> bool {
         point.x >= 0.0
             && point.y >= 0.0
@@ -178,14 +494,12 @@ impl Canvas {
         let mut texture = texture_creator
             .create_texture_streaming(PixelFormatEnum::ARGB8888, width, height)
             .expect("Failed to create a texture for the canvas");
+        texture.set_blend_mode(BlendMode::Blend);
 
         texture
             .with_lock(None, |data, _| {
-                for chunk in data.chunks_mut(4) {
-                    chunk[0] = 100;
-                    chunk[1] = 100;
-                    chunk[2] = 100;
-                    chunk[3] = 255;
+                for byte in data.iter_mut() {
+                    *byte = 0;
                 }
             })
             .expect("Failed to initialize the texture");
@@ -193,29 +507,180 @@ impl Canvas {
         texture
     }
 
+    /// Blends all visible layers within the given rectangle into the composite buffer.
+    fn composite_rect(&mut self, rect: Rect) {
+        let width = self.width as usize;
+        let left = rect.left() as usize;
+        let right = rect.right() as usize;
+        let mut preview_scratch = Vec::new();
+        for y in rect.top() as usize..rect.bottom() as usize {
+            let row_start = (y * width + left) * Self::BPP;
+            let row_end = (y * width + right) * Self::BPP;
+            let dst_row = &mut self.composite[row_start..row_end];
+            for pixel in dst_row.iter_mut() {
+                *pixel = 0;
+            }
+            let preview_row = self.preview.get(&(y as u32));
+            for (index, layer) in self.layers.iter().enumerate() {
+                if !layer.visible {
+                    continue;
+                }
+                let mut src_row = &layer.data[row_start..row_end];
+                if let (true, Some(spans)) = (index == self.active_layer, preview_row) {
+                    preview_scratch.clear();
+                    preview_scratch.extend_from_slice(src_row);
+                    for span in spans.iter() {
+                        let start = (span.left as usize).max(left);
+                        let end = (span.right as usize).min(right);
+                        for x in start..end {
+                            let color = match &span.pixels {
+                                PreviewPixels::Solid(color) => *color,
+                                PreviewPixels::Each(colors) => colors[x - span.left as usize],
+                            };
+                            let offset = (x - left) * Self::BPP;
+                            Self::color_to_slice(
+                                color,
+                                &mut preview_scratch[offset..offset + Self::BPP],
+                            );
+                        }
+                    }
+                    src_row = &preview_scratch;
+                }
+                composite_row(dst_row, src_row, layer);
+            }
+        }
+    }
+
+    /// Updates the texture used when zoomed out. Instead of letting SDL pick every n-th pixel,
+    /// each texture pixel is an alpha-weighted average of the image pixels it covers.
+    fn update_downsampled_texture(&mut self, numerator: u32, denominator: u32, visible_rect: Rect) {
+        let scale = Scale::fraction(numerator, denominator);
+        if self.downsampled_texture.as_ref().map(|(s, _)| *s) != Some(scale) {
+            let texture_width = scale.apply(self.width).max(1);
+            let texture_height = scale.apply(self.height).max(1);
+            let texture =
+                Self::create_sdl_texture(self.texture_creator, texture_width, texture_height);
+            self.downsampled_texture = Some((scale, texture));
+            self.downsampled_dirty.mark_all();
+        }
+
+        let visible_rect = match visible_rect.intersection(Rect::new(0, 0, self.width, self.height))
+        {
+            Some(rect) => rect,
+            None => return,
+        };
+        for rect in self.downsampled_dirty.take_within(visible_rect) {
+            self.downsample_rect(numerator, denominator, rect);
+        }
+    }
+
+    /// Updates the part of the downsampled texture which covers the given rectangle of the
+    /// canvas.
+    fn downsample_rect(&mut self, numerator: u32, denominator: u32, rect: Rect) {
+        let (num, den) = (numerator as u64, denominator as u64);
+        let scale_down = |coord: u64| coord * num / den;
+        let scale_down_ceil = |coord: u64| (coord * num).div_ceil(den);
+        let scale_up = |coord: u64| coord * den / num;
+        let texture_width = scale_down(self.width as u64).max(1) as u32;
+        let texture_height = scale_down(self.height as u64).max(1) as u32;
+
+        let dst_left = scale_down(rect.left() as u64).min(texture_width as u64 - 1);
+        let dst_top = scale_down(rect.top() as u64).min(texture_height as u64 - 1);
+        let dst_right = scale_down_ceil(rect.right() as u64)
+            .min(texture_width as u64)
+            .max(dst_left + 1);
+        let dst_bottom = scale_down_ceil(rect.bottom() as u64)
+            .min(texture_height as u64)
+            .max(dst_top + 1);
+        let src_range = |dst: u64, limit: u32| {
+            let start = scale_up(dst).min(limit as u64 - 1);
+            let end = scale_up(dst + 1).min(limit as u64).max(start + 1);
+            start..end
+        };
+
+        let src_left = src_range(dst_left, self.width).start;
+        let src_top = src_range(dst_top, self.height).start;
+        let src_right = src_range(dst_right - 1, self.width).end;
+        let src_bottom = src_range(dst_bottom - 1, self.height).end;
+        self.composite_rect(Rect::new(
+            src_left as i32,
+            src_top as i32,
+            (src_right - src_left) as u32,
+            (src_bottom - src_top) as u32,
+        ));
+
+        let dst_width = (dst_right - dst_left) as usize;
+        let dst_height = (dst_bottom - dst_top) as usize;
+        let mut buf = Vec::with_capacity(dst_width * dst_height * Self::BPP);
+        for dst_y in dst_top..dst_bottom {
+            for dst_x in dst_left..dst_right {
+                let mut sums = [0u64; 4];
+                let mut count = 0;
+                for src_y in src_range(dst_y, self.height) {
+                    for src_x in src_range(dst_x, self.width) {
+                        let offset =
+                            (src_y as usize * self.width as usize + src_x as usize) * Self::BPP;
+                        let pixel = &self.composite[offset..offset + Self::BPP];
+                        let alpha = pixel[3] as u64;
+                        for channel in 0..3 {
+                            sums[channel] += pixel[channel] as u64 * alpha;
+                        }
+                        sums[3] += alpha;
+                        count += 1;
+                    }
+                }
+                let alpha = sums[3];
+                for &sum in &sums[..3] {
+                    buf.push(sum.checked_div(alpha).unwrap_or(0) as u8);
+                }
+                buf.push((alpha / count) as u8);
+            }
+        }
+
+        let dst_rect = Rect::new(
+            dst_left as i32,
+            dst_top as i32,
+            dst_width as u32,
+            dst_height as u32,
+        );
+        self.downsampled_texture
+            .as_mut()
+            .unwrap()
+            .1
+            .update(dst_rect, &buf, dst_width * Self::BPP)
+            .expect("Failed to fill the texture with the image data");
+    }
+
     fn update_sdl_texture(&mut self, visible_rect: Rect) {
-        let visible_rect = visible_rect
-            .intersection(Rect::new(0, 0, self.width, self.height))
-            .unwrap();
+        let visible_rect = match visible_rect.intersection(Rect::new(0, 0, self.width, self.height))
+        {
+            Some(rect) => rect,
+            None => return,
+        };
+        for rect in self.dirty.take_within(visible_rect) {
+            self.upload_rect(rect);
+        }
+    }
+
+    /// Composites the given rectangle and copies it into the full-size texture.
+    fn upload_rect(&mut self, rect: Rect) {
+        self.composite_rect(rect);
         let start_offset = self
-            .calc_offset(visible_rect.left() as u32, visible_rect.top() as u32)
+            .calc_offset(rect.left() as u32, rect.top() as u32)
             .unwrap();
         let end_offset = self
-            .calc_offset(
-                visible_rect.right() as u32 - 1,
-                visible_rect.bottom() as u32 - 1,
-            )
+            .calc_offset(rect.right() as u32 - 1, rect.bottom() as u32 - 1)
             .unwrap();
-        let slice = &self.data[start_offset..=end_offset];
+        let slice = &self.composite[start_offset..end_offset + Self::BPP];
         let pitch_pixels = self.width as usize;
         let pitch = pitch_pixels * Self::BPP;
 
         // Workaround due to numerous bugs in the input validation in "safe" sdl2 API,
         // which lead to undefined behavior in case of wrong input.
-        //assert!(slice.len() >= pitch * visible_rect.height() as usize);
+        //assert!(slice.len() >= pitch * rect.height() as usize);
 
         self.texture
-            .update(visible_rect, slice, pitch)
+            .update(rect, slice, pitch)
             .expect("Failed to fill the texture with the image data");
     }
 
@@ -231,5 +696,6 @@ impl Canvas {
         }
     }
 
-    const BPP: usize = 4;
+    pub const BPP: usize = 4;
+    const CHECKERBOARD_CELL_SIZE: u32 = 8;
 }
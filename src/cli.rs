@@ -0,0 +1,323 @@
+use crate::batch::{Axis, Job, Operation};
+use crate::editor::Editor;
+use crate::image_io::{self, Format};
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage:
+    oxipaint [FILE]
+    oxipaint --new WIDTHxHEIGHT
+    oxipaint --print-keybindings
+    oxipaint --batch INPUT [OPERATION...] --output OUTPUT [--format FORMAT] [--plain]
+
+Batch operations are applied in the order they are given:
+    --resize WIDTHxHEIGHT     scale the image with nearest neighbour sampling
+    --flip horizontal|vertical
+
+The output format is picked from the output file extension, unless --format is given.
+--plain selects the plain text variant of PPM and PGM, and uncompressed TGA.
+
+--history-budget MIB sets how many mebibytes the undo history may take before its oldest
+entries are forgotten (256 by default). Projects are converted with --batch without loading
+their history, so it cannot be combined with --batch.
+
+--print-keybindings prints the default keybindings in the format of the keybindings.conf
+file in the OxiPaint configuration directory.
+";
+
+/// What the program has been asked to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Open the editor, optionally with a file or a blank canvas of the given size.
+    Interactive {
+        path: Option<PathBuf>,
+        size: Option<(u32, u32)>,
+        /// The memory budget of the undo history, in bytes.
+        history_budget: usize,
+    },
+    /// Process an image without opening a window.
+    Batch(Job),
+    Help,
+    PrintKeybindings,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UsageError(String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(formatter, "{}", self.0)
+    }
+}
+
+impl Error for UsageError {}
+
+/// Parses the command line arguments, not including the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, UsageError> {
+    let mut args = args.into_iter();
+    let mut path = None;
+    let mut size = None;
+    let mut batch_input = None;
+    let mut output = None;
+    let mut format = None;
+    let mut plain = false;
+    let mut history_budget = None;
+    let mut operations = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| UsageError(format!("{} expects a value", arg)))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--print-keybindings" => return Ok(Command::PrintKeybindings),
+            "--new" => size = Some(parse_size(&value()?)?),
+            "--batch" => batch_input = Some(PathBuf::from(value()?)),
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "--format" => {
+                let name = value()?;
+                format = Some(
+                    Format::ALL
+                        .iter()
+                        .copied()
+                        .find(|format| format.extensions().contains(&name.as_str()))
+                        .ok_or_else(|| UsageError(format!("Unknown format: {}", name)))?,
+                );
+            }
+            "--plain" => plain = true,
+            "--history-budget" => history_budget = Some(parse_budget(&value()?)?),
+            "--resize" => {
+                let (width, height) = parse_size(&value()?)?;
+                operations.push(Operation::Resize { width, height });
+            }
+            "--flip" => {
+                let axis = match value()?.as_str() {
+                    "horizontal" | "h" => Axis::Horizontal,
+                    "vertical" | "v" => Axis::Vertical,
+                    other => return Err(UsageError(format!("Unknown flip direction: {}", other))),
+                };
+                operations.push(Operation::Flip(axis));
+            }
+            _ if arg.starts_with('-') => {
+                return Err(UsageError(format!("Unknown option: {}", arg)))
+            }
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => return Err(UsageError(format!("Unexpected argument: {}", arg))),
+        }
+    }
+
+    match batch_input {
+        Some(input) => {
+            if path.is_some() || size.is_some() || history_budget.is_some() {
+                return Err(UsageError(
+                    "--batch cannot be combined with a file to open, --new or --history-budget"
+                        .to_owned(),
+                ));
+            }
+            let output = output.ok_or_else(|| UsageError("--batch needs --output".to_owned()))?;
+            let format = match format {
+                Some(format) => format,
+                None => Format::from_path(&output).ok_or_else(|| {
+                    UsageError(format!(
+                        "Cannot tell the format of {}, use --format",
+                        output.display()
+                    ))
+                })?,
+            };
+            Ok(Command::Batch(Job {
+                input,
+                output,
+                format,
+                plain,
+                operations,
+            }))
+        }
+        None => {
+            if output.is_some() || format.is_some() || plain || !operations.is_empty() {
+                return Err(UsageError(
+                    "Output options and operations are only allowed with --batch".to_owned(),
+                ));
+            }
+            if path.is_some() && size.is_some() {
+                return Err(UsageError(
+                    "Cannot both open a file and create a new canvas".to_owned(),
+                ));
+            }
+            Ok(Command::Interactive {
+                path,
+                size,
+                history_budget: history_budget.unwrap_or(Editor::DEFAULT_HISTORY_BUDGET),
+            })
+        }
+    }
+}
+
+/// Parses sizes like `800x600`.
+fn parse_size(text: &str) -> Result<(u32, u32), UsageError> {
+    let invalid = || UsageError(format!("Invalid size: {} (expected WIDTHxHEIGHT)", text));
+    let mut parts = text.splitn(2, ['x', 'X']);
+    let width: u32 = parts
+        .next()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(invalid)?;
+    let height: u32 = parts
+        .next()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(invalid)?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    if image_io::checked_data_size(width, height).is_err() {
+        return Err(UsageError(format!("Size {} is too large", text)));
+    }
+    Ok((width, height))
+}
+
+/// Parses a number of mebibytes into a number of bytes.
+fn parse_budget(text: &str) -> Result<usize, UsageError> {
+    text.parse::<usize>()
+        .ok()
+        .and_then(|mebibytes| mebibytes.checked_mul(1024 * 1024))
+        .ok_or_else(|| UsageError(format!("Invalid history budget: {}", text)))
+}
+
+/// Checks that a canvas of the size given with `--new` fits into a texture, whose maximum
+/// size is only known once the renderer has been created. A zero maximum means no limit.
+pub fn check_canvas_size(
+    (width, height): (u32, u32),
+    (max_width, max_height): (u32, u32),
+) -> Result<(), UsageError> {
+    if (max_width != 0 && width > max_width) || (max_height != 0 && height > max_height) {
+        return Err(UsageError(format!(
+            "Size {}x{} is too large, the renderer supports up to {}x{}",
+            width, height, max_width, max_height
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Command, UsageError> {
+        parse(args.iter().map(|&arg| arg.to_owned()))
+    }
+
+    #[test]
+    fn interactive() {
+        assert_eq!(
+            parse_args(&[]),
+            Ok(Command::Interactive {
+                path: None,
+                size: None,
+                history_budget: Editor::DEFAULT_HISTORY_BUDGET,
+            })
+        );
+        assert_eq!(
+            parse_args(&["image.png", "--history-budget", "16"]),
+            Ok(Command::Interactive {
+                path: Some(PathBuf::from("image.png")),
+                size: None,
+                history_budget: 16 * 1024 * 1024,
+            })
+        );
+        assert_eq!(
+            parse_args(&["--new", "640X480"]),
+            Ok(Command::Interactive {
+                path: None,
+                size: Some((640, 480)),
+                history_budget: Editor::DEFAULT_HISTORY_BUDGET,
+            })
+        );
+        assert_eq!(
+            parse_args(&["--print-keybindings"]),
+            Ok(Command::PrintKeybindings)
+        );
+        assert_eq!(parse_args(&["image.png", "--help"]), Ok(Command::Help));
+    }
+
+    #[test]
+    fn batch() {
+        assert_eq!(
+            parse_args(&[
+                "--batch", "in.oxp", "--resize", "10x20", "--flip", "h", "--flip", "vertical",
+                "-o", "out.ppm", "--plain",
+            ]),
+            Ok(Command::Batch(Job {
+                input: PathBuf::from("in.oxp"),
+                output: PathBuf::from("out.ppm"),
+                format: Format::Ppm,
+                plain: true,
+                operations: vec![
+                    Operation::Resize {
+                        width: 10,
+                        height: 20
+                    },
+                    Operation::Flip(Axis::Horizontal),
+                    Operation::Flip(Axis::Vertical),
+                ],
+            }))
+        );
+        let job = parse_args(&["--batch", "in.png", "--output", "out", "--format", "tga"]);
+        assert!(matches!(
+            job,
+            Ok(Command::Batch(Job {
+                format: Format::Tga,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let invalid = [
+            &["--new"][..],
+            &["--new", "640"],
+            &["--new", "0x480"],
+            &["--new", "-1x480"],
+            &["--new", "100000x100000"],
+            &["--new", "10x10", "image.png"],
+            &["a.png", "b.png"],
+            &["--bogus"],
+            &["--history-budget", "lots"],
+            &[
+                "--batch",
+                "in.oxp",
+                "-o",
+                "out.png",
+                "--history-budget",
+                "16",
+            ],
+            &["--history-budget", "-1"],
+            &["--history-budget", "99999999999999999999"],
+            &["--resize", "10x10"],
+            &["--batch", "in.png"],
+            &["--batch", "in.png", "-o", "out"],
+            &["--batch", "in.png", "-o", "out.png", "--format", "gif"],
+            &["--batch", "in.png", "-o", "out.png", "--flip", "diagonal"],
+            &[
+                "--batch",
+                "in.png",
+                "-o",
+                "out.png",
+                "--resize",
+                "65536x65536",
+            ],
+        ];
+        for args in invalid.iter() {
+            assert!(parse_args(args).is_err(), "{:?} has been accepted", args);
+        }
+    }
+
+    #[test]
+    fn canvas_size_limits() {
+        assert!(check_canvas_size((4096, 4096), (4096, 4096)).is_ok());
+        assert!(check_canvas_size((5000, 100), (0, 0)).is_ok());
+        assert!(check_canvas_size((4097, 100), (4096, 4096)).is_err());
+        assert!(check_canvas_size((100, 8193), (0, 8192)).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+use crate::overlay::{EventResponse, Overlay};
+use crate::text::Align;
+use crate::{SdlApp, SdlError};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+/// An entry of the command palette.
+pub struct PaletteEntry {
+    pub action: String,
+    pub title: String,
+    /// The keys bound to the action, or an empty string.
+    pub shortcut: String,
+}
+
+/// A list of commands at the top of the window, filtered by what the user types.
+pub struct CommandPalette {
+    commands: Vec<PaletteEntry>,
+    query: String,
+    /// Indices of the commands which match the query.
+    matches: Vec<usize>,
+    selected: usize,
+    /// The action picked with Enter, to be run once the palette closes.
+    chosen: Option<String>,
+}
+
+impl CommandPalette {
+    pub fn new(commands: Vec<PaletteEntry>) -> CommandPalette {
+        let mut palette = CommandPalette {
+            commands,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            chosen: None,
+        };
+        palette.update_matches();
+        palette
+    }
+
+    /// Keeps the commands whose title or action name contains every word of the query.
+    fn update_matches(&mut self) {
+        let words: Vec<String> = self
+            .query
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+        self.matches = self
+            .commands
+            .iter()
+            .enumerate()
+            .filter(|(_, command)| {
+                let haystack = format!("{} {}", command.title, command.action).to_lowercase();
+                words.iter().all(|word| haystack.contains(word.as_str()))
+            })
+            .map(|(index, _)| index)
+            .collect();
+        self.selected = 0;
+    }
+
+    const TOP: u32 = 40;
+    const WIDTH: u32 = 480;
+    const ROW_HEIGHT: u32 = 26;
+    const PADDING: i32 = 8;
+    const FONT_SIZE: u16 = 15;
+    const BACKGROUND_COLOR: Color = Color::RGB(230, 230, 230);
+    const SELECTED_COLOR: Color = Color::RGB(60, 110, 200);
+}
+
+impl Overlay for CommandPalette {
+    fn handle_event(&mut self, event: &Event) -> EventResponse {
+        match event {
+            Event::TextInput { text, .. } => {
+                self.query.push_str(text);
+                self.update_matches();
+            }
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } => match *key {
+                Keycode::Escape => return EventResponse::Close,
+                Keycode::Return | Keycode::KpEnter => {
+                    self.chosen = self
+                        .matches
+                        .get(self.selected)
+                        .map(|&index| self.commands[index].action.clone());
+                    return EventResponse::Close;
+                }
+                Keycode::Backspace => {
+                    self.query.pop();
+                    self.update_matches();
+                }
+                Keycode::Up => self.selected = self.selected.saturating_sub(1),
+                Keycode::Down => {
+                    self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1))
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        EventResponse::Consumed
+    }
+
+    fn draw(&mut self, sdl_app: &mut SdlApp) -> Result<(), SdlError> {
+        let mut canvas = sdl_app.sdl_canvas.borrow_mut();
+        let text = &mut sdl_app.text;
+        let (screen_width, screen_height) = canvas.window().drawable_size();
+
+        // The first row holds the query, the rest list the matching commands.
+        let width = Self::WIDTH.min(screen_width);
+        let left = (screen_width - width) as i32 / 2;
+        let max_rows = (screen_height.saturating_sub(Self::TOP) / Self::ROW_HEIGHT)
+            .saturating_sub(1)
+            .max(1) as usize;
+        let first_match = (self.selected + 1).saturating_sub(max_rows);
+        let rows = self.matches.len().min(max_rows);
+        let row_rect = |row: usize| {
+            Rect::new(
+                left,
+                (Self::TOP + row as u32 * Self::ROW_HEIGHT) as i32,
+                width,
+                Self::ROW_HEIGHT,
+            )
+        };
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_rect(Rect::new(
+            left - 1,
+            Self::TOP as i32 - 1,
+            width + 2,
+            (rows as u32 + 1) * Self::ROW_HEIGHT + 2,
+        ))?;
+        canvas.set_draw_color(Color::WHITE);
+        canvas.fill_rect(row_rect(0))?;
+        text.draw_in(
+            &mut canvas,
+            &format!("> {}", self.query),
+            Self::FONT_SIZE,
+            Color::BLACK,
+            row_rect(0),
+            Align::Left(Self::PADDING),
+        )?;
+
+        for (row, &index) in self.matches[first_match..first_match + rows]
+            .iter()
+            .enumerate()
+        {
+            let rect = row_rect(row + 1);
+            let (background, foreground) = if first_match + row == self.selected {
+                (Self::SELECTED_COLOR, Color::WHITE)
+            } else {
+                (Self::BACKGROUND_COLOR, Color::BLACK)
+            };
+            canvas.set_draw_color(background);
+            canvas.fill_rect(rect)?;
+            let command = &self.commands[index];
+            text.draw_in(
+                &mut canvas,
+                &command.title,
+                Self::FONT_SIZE,
+                foreground,
+                rect,
+                Align::Left(Self::PADDING),
+            )?;
+            text.draw_in(
+                &mut canvas,
+                &command.shortcut,
+                Self::FONT_SIZE,
+                foreground,
+                rect,
+                Align::Right(Self::PADDING),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn is_modal(&self) -> bool {
+        true
+    }
+
+    fn take_action(&mut self) -> Option<String> {
+        self.chosen.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::keyboard::Mod;
+
+    fn palette() -> CommandPalette {
+        let entry = |action: &str, title: &str| PaletteEntry {
+            action: action.to_owned(),
+            title: title.to_owned(),
+            shortcut: String::new(),
+        };
+        CommandPalette::new(vec![
+            entry("zoom_in", "Zoom in"),
+            entry("zoom_out", "Zoom out"),
+            entry("new_layer", "New layer"),
+            entry("save", "Save file"),
+        ])
+    }
+
+    fn matching_actions(palette: &CommandPalette) -> Vec<&str> {
+        palette
+            .matches
+            .iter()
+            .map(|&index| palette.commands[index].action.as_str())
+            .collect()
+    }
+
+    fn type_text(palette: &mut CommandPalette, text: &str) {
+        let event = Event::TextInput {
+            timestamp: 0,
+            window_id: 0,
+            text: text.to_owned(),
+        };
+        assert_eq!(palette.handle_event(&event), EventResponse::Consumed);
+    }
+
+    fn press(palette: &mut CommandPalette, key: Keycode) -> EventResponse {
+        palette.handle_event(&Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(key),
+            scancode: None,
+            keymod: Mod::empty(),
+            repeat: false,
+        })
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        let palette = palette();
+        assert_eq!(
+            matching_actions(&palette),
+            ["zoom_in", "zoom_out", "new_layer", "save"]
+        );
+    }
+
+    #[test]
+    fn every_word_must_match() {
+        let mut palette = palette();
+        type_text(&mut palette, "zoom");
+        assert_eq!(matching_actions(&palette), ["zoom_in", "zoom_out"]);
+        // The words may match in any order, in the title or in the action name.
+        type_text(&mut palette, " out");
+        assert_eq!(matching_actions(&palette), ["zoom_out"]);
+        palette.query = "  file   save ".to_owned();
+        palette.update_matches();
+        assert_eq!(matching_actions(&palette), ["save"]);
+        palette.query = "new_layer".to_owned();
+        palette.update_matches();
+        assert_eq!(matching_actions(&palette), ["new_layer"]);
+    }
+
+    #[test]
+    fn ignore_case() {
+        let mut palette = palette();
+        type_text(&mut palette, "ZoOm IN");
+        assert_eq!(matching_actions(&palette), ["zoom_in"]);
+        palette.query = "save FILE".to_owned();
+        palette.update_matches();
+        assert_eq!(matching_actions(&palette), ["save"]);
+    }
+
+    #[test]
+    fn reset_selection() {
+        let mut palette = palette();
+        press(&mut palette, Keycode::Down);
+        press(&mut palette, Keycode::Down);
+        assert_eq!(palette.selected, 2);
+        type_text(&mut palette, "o");
+        assert_eq!(palette.selected, 0);
+
+        press(&mut palette, Keycode::Down);
+        assert_eq!(palette.selected, 1);
+        press(&mut palette, Keycode::Backspace);
+        assert_eq!(palette.selected, 0);
+
+        // The selection follows the filtered list, so Enter picks the first match.
+        press(&mut palette, Keycode::Down);
+        type_text(&mut palette, "out");
+        assert_eq!(press(&mut palette, Keycode::Return), EventResponse::Close);
+        assert_eq!(palette.take_action(), Some("zoom_out".to_owned()));
+    }
+}
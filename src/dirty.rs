@@ -0,0 +1,128 @@
+use crate::tile_grid::TileGrid;
+use sdl2::rect::Rect;
+
+/// Keeps track of the parts of the canvas which have changed since they were last uploaded
+/// to a texture.
+pub struct DirtyTiles {
+    grid: TileGrid,
+    tiles: Vec<bool>,
+}
+
+impl DirtyTiles {
+    /// Creates a set in which the whole canvas is dirty.
+    pub fn new(width: u32, height: u32) -> DirtyTiles {
+        let grid = TileGrid::new(width, height, Self::TILE_SIZE);
+        DirtyTiles {
+            grid,
+            tiles: vec![true; grid.tile_count()],
+        }
+    }
+
+    pub fn mark_at(&mut self, x: u32, y: u32) {
+        let index = self.grid.index_at(x, y);
+        self.tiles[index] = true;
+    }
+
+    /// Marks the given rectangle, which must lie within the canvas.
+    pub fn mark_rect(&mut self, rect: Rect) {
+        let (columns, rows) = self.grid.span(rect);
+        for row in rows {
+            for column in columns.clone() {
+                self.tiles[(row * self.grid.columns() + column) as usize] = true;
+            }
+        }
+    }
+
+    pub fn mark_all(&mut self) {
+        for tile in self.tiles.iter_mut() {
+            *tile = true;
+        }
+    }
+
+    /// Unmarks the dirty tiles which intersect the given rectangle and returns the areas they
+    /// cover, merging neighbours within a row. The rectangle must lie within the canvas.
+    pub fn take_within(&mut self, rect: Rect) -> Vec<Rect> {
+        let (columns, rows) = self.grid.span(rect);
+        let mut taken = Vec::new();
+        for row in rows {
+            let mut run: Option<Rect> = None;
+            for column in columns.clone() {
+                let index = (row * self.grid.columns() + column) as usize;
+                if !self.tiles[index] {
+                    taken.extend(run.take());
+                    continue;
+                }
+                self.tiles[index] = false;
+                let tile = self.grid.tile_rect(index);
+                run = Some(match run {
+                    Some(run) => run.union(tile),
+                    None => tile,
+                });
+            }
+            taken.extend(run);
+        }
+        taken
+    }
+
+    const TILE_SIZE: u32 = 64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_touched_tiles() {
+        let whole = Rect::new(0, 0, 200, 100);
+        let mut dirty = DirtyTiles::new(200, 100);
+        assert_eq!(
+            dirty.take_within(whole),
+            vec![Rect::new(0, 0, 200, 64), Rect::new(0, 64, 200, 36)]
+        );
+        assert_eq!(dirty.take_within(whole), vec![]);
+
+        dirty.mark_at(199, 0);
+        dirty.mark_rect(Rect::new(60, 70, 5, 1));
+        assert_eq!(
+            dirty.take_within(whole),
+            vec![Rect::new(192, 0, 8, 64), Rect::new(0, 64, 128, 36),]
+        );
+        assert_eq!(dirty.take_within(whole), vec![]);
+
+        dirty.mark_all();
+        assert_eq!(dirty.tiles, vec![true; 8]);
+    }
+
+    #[test]
+    fn coalesce_within_rows() {
+        let whole = Rect::new(0, 0, 300, 200);
+        let mut dirty = DirtyTiles::new(300, 200);
+        dirty.take_within(whole);
+
+        // Row 0: a run of two tiles and a separate one. Row 1: a single tile. Row 3: the
+        // last, smaller tile.
+        for &(x, y) in [(0, 0), (64, 10), (200, 0), (130, 64), (299, 199)].iter() {
+            dirty.mark_at(x, y);
+        }
+        assert_eq!(
+            dirty.take_within(whole),
+            vec![
+                Rect::new(0, 0, 128, 64),
+                Rect::new(192, 0, 64, 64),
+                Rect::new(128, 64, 64, 64),
+                Rect::new(256, 192, 44, 8),
+            ]
+        );
+
+        // Only the tiles within the rectangle are taken, the rest stay dirty.
+        dirty.mark_rect(Rect::new(0, 0, 300, 64));
+        assert_eq!(
+            dirty.take_within(Rect::new(100, 10, 100, 10)),
+            vec![Rect::new(64, 0, 192, 64)]
+        );
+        assert_eq!(
+            dirty.take_within(whole),
+            vec![Rect::new(0, 0, 64, 64), Rect::new(256, 0, 44, 64)]
+        );
+    }
+}
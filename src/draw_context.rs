@@ -1,17 +1,62 @@
 use crate::TranslatedPoint;
+use sdl2::keyboard::Mod;
+use sdl2::mouse::MouseButton;
 use sdl2::pixels::Color;
 
 #[derive(Debug, Clone, Copy)]
 pub struct DrawContext {
     pub primary_color: Color,
+    pub secondary_color: Color,
     pub cursor_position: TranslatedPoint,
+    pub keymod: Mod,
+    pub brush_size: u32,
+    pub eraser_size: u32,
+    pub fill_tolerance: u8,
+    pub fill_connectivity: Connectivity,
+    pub fill_global: bool,
+    pub picker_size: u32,
+    pub shape_filled: bool,
+}
+
+impl DrawContext {
+    /// Returns the color which a tool should use when operated with the given mouse button.
+    pub fn color_for(&self, button: MouseButton) -> Option<Color> {
+        match button {
+            MouseButton::Left => Some(self.primary_color),
+            MouseButton::Right => Some(self.secondary_color),
+            _ => None,
+        }
+    }
+
+    pub fn color_for_mut(&mut self, button: MouseButton) -> Option<&mut Color> {
+        match button {
+            MouseButton::Left => Some(&mut self.primary_color),
+            MouseButton::Right => Some(&mut self.secondary_color),
+            _ => None,
+        }
+    }
 }
 
 impl Default for DrawContext {
     fn default() -> DrawContext {
         DrawContext {
             primary_color: Color::BLACK,
+            secondary_color: Color::WHITE,
             cursor_position: TranslatedPoint::OutsideWindow,
+            keymod: Mod::NOMOD,
+            brush_size: 1,
+            eraser_size: 8,
+            fill_tolerance: 0,
+            fill_connectivity: Connectivity::Four,
+            fill_global: false,
+            picker_size: 1,
+            shape_filled: false,
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
@@ -114,3 +114,343 @@ impl HardLine {
         Rectangle::new(left, top, width, height)
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Disc {
+    center: Point,
+    diameter: f64,
+}
+
+impl Disc {
+    pub fn try_new(center: Point, diameter: f64) -> Option<Disc> {
+        if diameter > 0.0 {
+            Some(Disc { center, diameter })
+        } else {
+            None
+        }
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        let radius = self.diameter / 2.0;
+        (point.x - self.center.x).hypot(point.y - self.center.y) <= radius
+    }
+
+    /// Draws the disc. The pixel containing the center is always drawn, so that even
+    /// the smallest discs leave a mark.
+    pub fn draw(&self, put_pixel: &mut impl FnMut(u32, u32)) {
+        if self.center.x >= 0.0 && self.center.y >= 0.0 {
+            put_pixel(self.center.x as u32, self.center.y as u32);
+        }
+
+        let rect = self.bounding_box().bounding_int_rectangle();
+        for pixel_y in rect.top()..=rect.bottom() {
+            if pixel_y < 0 {
+                continue;
+            }
+
+            for pixel_x in rect.left()..=rect.right() {
+                if pixel_x < 0 {
+                    continue;
+                }
+
+                let point = Point::new(pixel_x as f64 + 0.5, pixel_y as f64 + 0.5);
+                if self.contains(point) {
+                    put_pixel(pixel_x as u32, pixel_y as u32);
+                }
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Rectangle {
+        let radius = self.diameter / 2.0;
+        Rectangle::new(
+            self.center.x - radius - 1.0,
+            self.center.y - radius - 1.0,
+            self.diameter + 2.0,
+            self.diameter + 2.0,
+        )
+    }
+}
+
+/// A thick line segment with round caps. Consecutive segments sharing their ends join
+/// without gaps or notches, which makes it suitable for freehand strokes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundLine {
+    a: Point,
+    b: Point,
+    thickness: f64,
+}
+
+impl RoundLine {
+    pub fn try_new(a: Point, b: Point, thickness: f64) -> Option<RoundLine> {
+        if thickness > 0.0 {
+            Some(RoundLine { a, b, thickness })
+        } else {
+            None
+        }
+    }
+
+    pub fn draw(&self, put_pixel: &mut impl FnMut(u32, u32)) {
+        for &end in &[self.a, self.b] {
+            if let Some(cap) = Disc::try_new(end, self.thickness) {
+                cap.draw(put_pixel);
+            }
+        }
+        if let Some(body) = HardLine::try_new(self.a, self.b, self.thickness) {
+            body.draw(put_pixel);
+        }
+    }
+}
+
+/// An axis-aligned rectangle spanning the pixels from (left, top) to (right, bottom) inclusive,
+/// either filled or outlined with the given thickness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelRect {
+    left: i64,
+    top: i64,
+    right: i64,
+    bottom: i64,
+    outline: Option<i64>,
+}
+
+impl PixelRect {
+    pub fn new(a: (i64, i64), b: (i64, i64), outline: Option<u32>) -> PixelRect {
+        PixelRect {
+            left: a.0.min(b.0),
+            top: a.1.min(b.1),
+            right: a.0.max(b.0),
+            bottom: a.1.max(b.1),
+            outline: outline.map(|thickness| thickness.max(1) as i64),
+        }
+    }
+
+    /// Draws the rectangle as horizontal runs of pixels, clipped to a canvas of the given size.
+    /// `put_span(y, left, right)` covers the pixels from `left` up to, but not including,
+    /// `right`.
+    pub fn draw_spans(&self, width: u32, height: u32, put_span: &mut impl FnMut(u32, u32, u32)) {
+        for pixel_y in self.top.max(0)..=self.bottom.min(height as i64 - 1) {
+            let full_row = match self.outline {
+                None => true,
+                Some(t) => {
+                    pixel_y < self.top + t
+                        || pixel_y > self.bottom - t
+                        || self.left + t > self.right - t
+                }
+            };
+            if full_row {
+                put_clipped_span(pixel_y, self.left, self.right, width, put_span);
+            } else {
+                let t = self.outline.unwrap();
+                put_clipped_span(pixel_y, self.left, self.left + t - 1, width, put_span);
+                put_clipped_span(pixel_y, self.right - t + 1, self.right, width, put_span);
+            }
+        }
+    }
+}
+
+/// An ellipse inscribed into a `PixelRect`, either filled or outlined with the given thickness.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ellipse {
+    bounds: PixelRect,
+    outline: Option<f64>,
+}
+
+impl Ellipse {
+    pub fn new(a: (i64, i64), b: (i64, i64), outline: Option<u32>) -> Ellipse {
+        Ellipse {
+            bounds: PixelRect::new(a, b, None),
+            outline: outline.map(|thickness| thickness.max(1) as f64),
+        }
+    }
+
+    fn is_inside(point: Point, center: Point, radius_x: f64, radius_y: f64) -> bool {
+        if radius_x <= 0.0 || radius_y <= 0.0 {
+            return false;
+        }
+        let dx = (point.x - center.x) / radius_x;
+        let dy = (point.y - center.y) / radius_y;
+        dx * dx + dy * dy <= 1.0
+    }
+
+    /// Returns the first and the last pixel of a row whose centers lie within the ellipse with
+    /// the given radii. The estimate is corrected with `is_inside`, so that the result matches
+    /// a pixel-by-pixel test exactly.
+    fn row_extent(y: i64, center: Point, radius_x: f64, radius_y: f64) -> Option<(i64, i64)> {
+        let inside = |x: i64| {
+            Self::is_inside(
+                Point::new(x as f64 + 0.5, y as f64 + 0.5),
+                center,
+                radius_x,
+                radius_y,
+            )
+        };
+        if radius_x <= 0.0 || radius_y <= 0.0 {
+            return None;
+        }
+        let dy = (y as f64 + 0.5 - center.y) / radius_y;
+        let half_width = radius_x * (1.0 - dy * dy).max(0.0).sqrt();
+        let mut left = (center.x - half_width - 0.5).ceil() as i64;
+        let mut right = (center.x + half_width - 0.5).floor() as i64;
+        while left <= right && !inside(left) {
+            left += 1;
+        }
+        while inside(left - 1) {
+            left -= 1;
+        }
+        while right >= left && !inside(right) {
+            right -= 1;
+        }
+        while inside(right + 1) {
+            right += 1;
+        }
+        if left <= right && inside(left) {
+            Some((left, right))
+        } else {
+            None
+        }
+    }
+
+    /// Draws the ellipse as horizontal runs of pixels, clipped to a canvas of the given size,
+    /// the same way as `PixelRect::draw_spans`.
+    pub fn draw_spans(&self, width: u32, height: u32, put_span: &mut impl FnMut(u32, u32, u32)) {
+        let b = &self.bounds;
+        let center = Point::new(
+            (b.left + b.right + 1) as f64 / 2.0,
+            (b.top + b.bottom + 1) as f64 / 2.0,
+        );
+        let radius_x = (b.right - b.left + 1) as f64 / 2.0;
+        let radius_y = (b.bottom - b.top + 1) as f64 / 2.0;
+        for pixel_y in b.top.max(0)..=b.bottom.min(height as i64 - 1) {
+            let (left, right) = match Self::row_extent(pixel_y, center, radius_x, radius_y) {
+                Some(extent) => extent,
+                None => continue,
+            };
+            let hole = self
+                .outline
+                .and_then(|t| Self::row_extent(pixel_y, center, radius_x - t, radius_y - t));
+            match hole {
+                None => put_clipped_span(pixel_y, left, right, width, put_span),
+                Some((hole_left, hole_right)) => {
+                    put_clipped_span(pixel_y, left, hole_left - 1, width, put_span);
+                    put_clipped_span(pixel_y, hole_right + 1, right, width, put_span);
+                }
+            }
+        }
+    }
+}
+
+/// Passes on the pixels from `left` to `right` inclusive which lie within the canvas width.
+fn put_clipped_span(
+    y: i64,
+    left: i64,
+    right: i64,
+    width: u32,
+    put_span: &mut impl FnMut(u32, u32, u32),
+) {
+    let left = left.max(0);
+    let right = right.min(width as i64 - 1);
+    if left <= right {
+        put_span(y as u32, left as u32, right as u32 + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn span_pixels(
+        draw_spans: impl Fn(&mut dyn FnMut(u32, u32, u32)),
+        width: u32,
+        height: u32,
+    ) -> BTreeSet<(u32, u32)> {
+        let mut pixels = BTreeSet::new();
+        draw_spans(&mut |y, left, right| {
+            assert!(left < right && right <= width && y < height);
+            for x in left..right {
+                assert!(pixels.insert((x, y)), "({}, {}) has been drawn twice", x, y);
+            }
+        });
+        pixels
+    }
+
+    /// The pixels whose centers lie within an ellipse, tested one by one.
+    fn ellipse_pixels(ellipse: &Ellipse, width: u32, height: u32) -> BTreeSet<(u32, u32)> {
+        let b = &ellipse.bounds;
+        let center = Point::new(
+            (b.left + b.right + 1) as f64 / 2.0,
+            (b.top + b.bottom + 1) as f64 / 2.0,
+        );
+        let radius_x = (b.right - b.left + 1) as f64 / 2.0;
+        let radius_y = (b.bottom - b.top + 1) as f64 / 2.0;
+        let mut pixels = BTreeSet::new();
+        for y in 0..height {
+            for x in 0..width {
+                let point = Point::new(x as f64 + 0.5, y as f64 + 0.5);
+                let inside_outer = Ellipse::is_inside(point, center, radius_x, radius_y);
+                let inside_inner = match ellipse.outline {
+                    None => false,
+                    Some(t) => Ellipse::is_inside(point, center, radius_x - t, radius_y - t),
+                };
+                if inside_outer && !inside_inner {
+                    pixels.insert((x, y));
+                }
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn rect_spans() {
+        let draw = |rect: PixelRect| {
+            span_pixels(
+                |put_span| rect.draw_spans(10, 8, &mut |y, l, r| put_span(y, l, r)),
+                10,
+                8,
+            )
+        };
+        let filled = draw(PixelRect::new((2, 1), (4, 3), None));
+        assert_eq!(filled.len(), 9);
+        let outline = draw(PixelRect::new((1, 1), (6, 5), Some(1)));
+        assert_eq!(outline.len(), 6 * 5 - 4 * 3);
+        assert!(!outline.contains(&(3, 3)));
+        // A rectangle thicker than it is wide is solid.
+        assert_eq!(draw(PixelRect::new((1, 1), (3, 6), Some(2))).len(), 3 * 6);
+        // Clipped to the canvas.
+        let clipped = draw(PixelRect::new((-5, -5), (1_000_000, 1_000_000), None));
+        assert_eq!(clipped.len(), 10 * 8);
+        assert!(draw(PixelRect::new((20, 20), (30, 30), None)).is_empty());
+    }
+
+    #[test]
+    fn ellipse_spans_match_pixels() {
+        let cases = [
+            ((0, 0), (9, 7), None),
+            ((1, 2), (14, 9), Some(1)),
+            ((-3, -4), (12, 11), Some(3)),
+            ((2, 2), (2, 2), None),
+            ((0, 0), (15, 15), Some(20)),
+            ((4, 1), (5, 30), Some(1)),
+        ];
+        for &(a, b, outline) in cases.iter() {
+            let ellipse = Ellipse::new(a, b, outline);
+            let spans = span_pixels(
+                |put_span| ellipse.draw_spans(16, 12, &mut |y, l, r| put_span(y, l, r)),
+                16,
+                12,
+            );
+            assert_eq!(spans, ellipse_pixels(&ellipse, 16, 12), "{:?}", ellipse);
+        }
+    }
+
+    #[test]
+    fn huge_shapes_are_clipped() {
+        let mut area = 0;
+        Ellipse::new((-100_000, -100_000), (100_000, 100_000), None).draw_spans(
+            64,
+            48,
+            &mut |_, left, right| area += right - left,
+        );
+        assert_eq!(area, 64 * 48);
+    }
+}
@@ -1,44 +1,136 @@
 use crate::canvas::Canvas;
 use crate::geometry::{Point, Scale};
-use crate::history::{DiffDirection, History};
+use crate::grid::Grid;
+use crate::history::{Diff, DiffDirection, History, NodeId};
+use crate::image_io::RgbaImage;
+use crate::layer::Layer;
+use crate::project::Project;
+use crate::selection::{Floating, PixelBuffer, Selection};
 use crate::SdlCanvas;
+use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 
 use std::cell::RefCell;
+use std::mem;
 use std::rc::Rc;
 
 pub struct Editor {
     canvas: Canvas,
-    shadow_data: Vec<u8>,
     history: History,
     in_transaction: bool,
     scale: Scale,
     center: Point,
+    selection: Option<Selection>,
+    marching_ants_phase: u32,
+    grid: Grid,
+    tool_name: String,
+    transaction_label: String,
+    /// The history node which was current when the image was last saved or opened.
+    saved_node: NodeId,
+    /// Whether layer properties, which are not recorded in the history, have changed since
+    /// the image was last saved or opened.
+    layer_properties_changed: bool,
+    history_budget: usize,
 }
 
 impl Editor {
     pub fn new(width: u32, height: u32, sdl_canvas: Rc<RefCell<SdlCanvas>>) -> Editor {
         let canvas = Canvas::new(width, height, sdl_canvas);
-        let shadow_data = canvas.create_shadow_data();
-        let history = History::new();
+        let history_budget = Self::DEFAULT_HISTORY_BUDGET;
+        let history = History::new(history_budget);
+        let saved_node = history.current();
         let in_transaction = false;
         let scale = Scale::Times(1);
         let center = Point::new(width as f64, height as f64).map(|x| x / 2.0);
 
         Editor {
             canvas,
-            shadow_data,
             history,
             in_transaction,
             scale,
             center,
+            selection: None,
+            marching_ants_phase: 0,
+            grid: Grid::default(),
+            tool_name: String::new(),
+            transaction_label: String::new(),
+            saved_node,
+            layer_properties_changed: false,
+            history_budget,
         }
     }
 
+    /// Replaces the canvas contents with a new image, starting over with a fresh history and
+    /// a centered view.
+    pub fn load_image(&mut self, image: &RgbaImage) -> Result<(), TimeMachineError> {
+        if self.in_transaction {
+            return Err(TimeMachineError::TransactionInProgress);
+        }
+        self.selection = None;
+        self.canvas.load_image(image);
+        self.history = History::new(self.history_budget);
+        self.mark_saved();
+        self.scale = Scale::Times(1);
+        self.center = Point::new(image.width as f64, image.height as f64).map(|x| x / 2.0);
+        Ok(())
+    }
+
+    /// Replaces the canvas contents with the project's layers. The view is restored if the
+    /// project has one, and the history is replaced with the given one or a fresh one.
+    pub fn load_project(
+        &mut self,
+        project: Project,
+        history: Option<History>,
+    ) -> Result<(), TimeMachineError> {
+        if self.in_transaction {
+            return Err(TimeMachineError::TransactionInProgress);
+        }
+        self.selection = None;
+        self.canvas.load_layers(
+            project.width,
+            project.height,
+            project.layers,
+            project.active_layer,
+        );
+        self.history = history.unwrap_or_else(|| History::new(self.history_budget));
+        self.mark_saved();
+        let (width, height) = (project.width, project.height);
+        let (scale, center) = project.view.unwrap_or_else(|| {
+            let center = Point::new(width as f64, height as f64);
+            (Scale::Times(1), center.map(|x| x / 2.0))
+        });
+        self.scale = scale;
+        self.center = center;
+        Ok(())
+    }
+
+    /// Describes the canvas and the view as a project. The tool and the colors are left
+    /// unset, since they are not known to the editor.
+    pub fn to_project(&self) -> Project {
+        Project {
+            width: self.canvas.width(),
+            height: self.canvas.height(),
+            layers: self.canvas.layers().to_vec(),
+            active_layer: self.canvas.active_layer_index(),
+            tool_name: None,
+            colors: None,
+            view: Some((self.scale, self.center)),
+        }
+    }
+
+    pub fn grid_mut(&mut self) -> &mut Grid {
+        &mut self.grid
+    }
+
     pub fn scale(&self) -> Scale {
         self.scale
     }
 
+    /// The image point shown in the middle of the window.
+    pub fn center(&self) -> Point {
+        self.center
+    }
+
     pub fn scroll(&mut self, delta_x: f64, delta_y: f64) {
         let width = self.canvas.width() as f64;
         let height = self.canvas.width() as f64;
@@ -50,18 +142,29 @@ impl Editor {
 
     fn recalc_scale_up(orig_scale: Scale) -> Option<Scale> {
         match orig_scale {
+            _ if orig_scale.factor() >= Scale::MAX.factor() => None,
             Scale::Times(n) => Some(Scale::Times(n + 1)),
+            Scale::Fraction { .. } => Some(
+                Scale::FRACTIONS
+                    .iter()
+                    .copied()
+                    .find(|s| s.factor() > orig_scale.factor())
+                    .unwrap_or(Scale::Times(1)),
+            ),
         }
     }
 
     fn recalc_scale_down(orig_scale: Scale) -> Option<Scale> {
         match orig_scale {
             Scale::Times(0) => unreachable!(),
-            Scale::Times(1) => None,
+            Scale::Times(1) | Scale::Fraction { .. } => Scale::FRACTIONS
+                .iter()
+                .rev()
+                .copied()
+                .find(|s| s.factor() < orig_scale.factor()),
             Scale::Times(n) => Some(Scale::Times(n - 1)),
         }
     }
-
     pub fn scale_up(&mut self, stationary_point: Point) -> Option<Scale> {
         let new_scale = Self::recalc_scale_up(self.scale);
         if let Some(s) = new_scale {
@@ -78,6 +181,25 @@ impl Editor {
         new_scale
     }
 
+    /// Sets the zoom level, keeping the current center of the view.
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.scale = scale;
+    }
+
+    /// Picks the zoom level at which the whole image fits into (or, with `ZoomFit::Fill`,
+    /// covers) the screen, and centers the view on the image.
+    pub fn zoom_to_fit(&mut self, screen_width: u32, screen_height: u32, fit: ZoomFit) -> Scale {
+        let ratio_x = screen_width as f64 / self.canvas.width() as f64;
+        let ratio_y = screen_height as f64 / self.canvas.height() as f64;
+        self.scale = match fit {
+            ZoomFit::Fit => Scale::at_most(ratio_x.min(ratio_y)),
+            ZoomFit::Fill => Scale::at_least(ratio_x.max(ratio_y)),
+        };
+        self.center =
+            Point::new(self.canvas.width() as f64, self.canvas.height() as f64).map(|x| x / 2.0);
+        self.scale
+    }
+
     fn rescale(&mut self, new_scale: Scale, stationary_point: Point) {
         let orig_scale = self.scale;
         let orig_center = self.center;
@@ -109,7 +231,100 @@ impl Editor {
         &mut self.canvas
     }
 
+    /// The number of bytes taken by the undo history.
+    pub fn history_memory_usage(&self) -> usize {
+        self.history.memory_usage()
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// The number of bytes the undo history may take before its oldest entries are forgotten.
+    pub fn history_budget(&self) -> usize {
+        self.history_budget
+    }
+
+    pub fn set_history_budget(&mut self, budget: usize) {
+        self.history_budget = budget;
+        self.history.set_memory_budget(budget);
+    }
+
+    /// Remembers the current state of the history as the one which has been saved.
+    pub fn mark_saved(&mut self) {
+        self.saved_node = self.history.current();
+        self.layer_properties_changed = false;
+    }
+
+    /// Whether the image differs from the one last saved or opened, as far as the history
+    /// and the layer properties can tell.
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.saved_node != self.history.current() || self.layer_properties_changed
+    }
+
+    /// Changes the properties of the active layer, such as its visibility, lock or opacity.
+    pub fn update_active_layer(&mut self, func: impl FnOnce(&mut Layer)) {
+        let index = self.canvas.active_layer_index();
+        func(self.canvas.layer_mut(index).unwrap());
+        self.layer_properties_changed = true;
+    }
+
+    /// Sets the name which labels the history entries recorded by `begin` and `end`.
+    pub fn set_tool_name(&mut self, name: String) {
+        self.tool_name = name;
+    }
+
+    /// Undoes and redoes diffs until the given node of the history tree becomes current.
+    pub fn jump_to(&mut self, target: NodeId) -> Result<(), TimeMachineError> {
+        self.commit_selection();
+        if self.in_transaction {
+            return Err(TimeMachineError::TransactionInProgress);
+        }
+        let (undo, redo) = self.history.path_to(target);
+        for id in undo {
+            if let Some(diff) = self.history.diff(id) {
+                self.canvas.apply_diff(diff, DiffDirection::Reverse);
+            }
+        }
+        for id in redo {
+            if let Some(diff) = self.history.diff(id) {
+                self.canvas.apply_diff(diff, DiffDirection::Normal);
+            }
+        }
+        self.history.set_current(target);
+        Ok(())
+    }
+
+    /// Switches to the sibling branch which is `delta` positions away from the current one.
+    pub fn switch_branch(&mut self, delta: isize) -> Result<(), TimeMachineError> {
+        let target = self
+            .history
+            .sibling(self.history.current(), delta)
+            .ok_or(TimeMachineError::AlreadyAtTimeEdge)?;
+        self.jump_to(target)
+    }
+
+    /// Moves `delta` steps backwards or forwards in time, regardless of the branches.
+    pub fn step_chronologically(&mut self, delta: isize) -> Result<(), TimeMachineError> {
+        let target = self
+            .history
+            .chronological_neighbour(self.history.current(), delta)
+            .ok_or(TimeMachineError::AlreadyAtTimeEdge)?;
+        self.jump_to(target)
+    }
+
+    pub fn add_checkpoint(&mut self, name: String) -> Result<(), TimeMachineError> {
+        self.commit_selection();
+        if self.in_transaction {
+            return Err(TimeMachineError::TransactionInProgress);
+        }
+        self.history.add_checkpoint(name);
+        Ok(())
+    }
+
     pub fn undo(&mut self) -> Result<(), TimeMachineError> {
+        // Undoing right after moving a selection around should revert the move.
+        self.commit_selection();
         if self.in_transaction {
             return Err(TimeMachineError::TransactionInProgress);
         }
@@ -122,6 +337,7 @@ impl Editor {
     }
 
     pub fn redo(&mut self) -> Result<(), TimeMachineError> {
+        self.commit_selection();
         if self.in_transaction {
             return Err(TimeMachineError::TransactionInProgress);
         }
@@ -133,14 +349,276 @@ impl Editor {
         Ok(())
     }
 
-    pub fn begin(&mut self) {
-        self.canvas.update_shadow_data(&mut self.shadow_data);
+    pub fn selection(&self) -> Option<&Selection> {
+        self.selection.as_ref()
+    }
+
+    /// Selects the given rectangle (clipped to the canvas), or deselects everything if `None`
+    /// is given. A floating selection is committed first.
+    pub fn select(&mut self, rect: Option<Rect>) {
+        self.commit_selection();
+        let canvas_rect = Rect::new(0, 0, self.canvas.width(), self.canvas.height());
+        self.selection = rect
+            .and_then(|rect| rect.intersection(canvas_rect))
+            .map(Selection::new);
+    }
+
+    /// Cuts the selected pixels out of the active layer into a floating selection, which can
+    /// then be moved around. Everything up to `commit_selection` is recorded as a single
+    /// history entry.
+    pub fn lift_selection(&mut self) -> Result<(), TimeMachineError> {
+        if self.in_transaction {
+            return Err(TimeMachineError::TransactionInProgress);
+        }
+        let rect = match &self.selection {
+            Some(selection) if !selection.is_floating() => selection.rect,
+            _ => return Ok(()),
+        };
+        self.begin_labelled("Move selection")?;
+        let buffer = PixelBuffer::copy_from(&self.canvas, rect);
+        self.clear_rect(rect);
+        self.float(buffer, rect.x(), rect.y());
+        Ok(())
+    }
+
+    pub fn move_floating_selection(&mut self, x: i32, y: i32) {
+        if let Some(selection) = &mut self.selection {
+            if let Some(floating) = &mut selection.floating {
+                floating.x = x;
+                floating.y = y;
+                selection.rect.reposition((x, y));
+                let pixels = floating.buffer.placed_at(x, y);
+                self.canvas.set_preview(pixels);
+            }
+        }
+    }
+
+    /// Stamps a floating selection back onto the active layer.
+    pub fn commit_selection(&mut self) {
+        let floating = match self.selection.as_mut().and_then(|s| s.floating.take()) {
+            Some(floating) => floating,
+            None => return,
+        };
+        self.canvas.clear_preview();
+        for (x, y, color) in floating.buffer.placed_at(floating.x, floating.y) {
+            self.canvas.try_set_at(x, y, color);
+        }
+        self.end();
+        let rect = self.selection.as_ref().unwrap().rect;
+        self.select(Some(rect));
+    }
+
+    pub fn copy_selection(&self) -> Option<PixelBuffer> {
+        let selection = self.selection.as_ref()?;
+        Some(match &selection.floating {
+            Some(floating) => floating.buffer.clone(),
+            None => PixelBuffer::copy_from(&self.canvas, selection.rect),
+        })
+    }
+
+    pub fn cut_selection(&mut self) -> Result<Option<PixelBuffer>, TimeMachineError> {
+        if matches!(&self.selection, Some(selection) if selection.is_floating()) {
+            // The pixels have already been lifted off the layer, so just drop them.
+            let buffer = self.copy_selection();
+            let selection = self.selection.as_mut().unwrap();
+            selection.floating = None;
+            self.canvas.clear_preview();
+            self.transaction_label = "Cut".to_owned();
+            self.end();
+            self.select(None);
+            return Ok(buffer);
+        }
+        if self.in_transaction {
+            return Err(TimeMachineError::TransactionInProgress);
+        }
+        let buffer = self.copy_selection();
+        if let Some(selection) = &self.selection {
+            let rect = selection.rect;
+            self.begin_labelled("Cut")?;
+            self.clear_rect(rect);
+            self.end();
+        }
+        Ok(buffer)
+    }
+
+    /// Pastes the buffer as a floating selection at the position of the current selection, or
+    /// at the top left corner of the canvas if nothing is selected.
+    pub fn paste(&mut self, buffer: PixelBuffer) -> Result<(), TimeMachineError> {
+        self.commit_selection();
+        if self.in_transaction {
+            return Err(TimeMachineError::TransactionInProgress);
+        }
+        let (x, y) = self
+            .selection
+            .as_ref()
+            .map_or((0, 0), |selection| (selection.rect.x(), selection.rect.y()));
+        self.begin_labelled("Paste")?;
+        self.float(buffer, x, y);
+        Ok(())
+    }
+
+    fn float(&mut self, buffer: PixelBuffer, x: i32, y: i32) {
+        let rect = Rect::new(x, y, buffer.width(), buffer.height());
+        self.selection = Some(Selection {
+            rect,
+            floating: Some(Floating { buffer, x, y }),
+        });
+        self.move_floating_selection(x, y);
+    }
+
+    fn clear_rect(&mut self, rect: Rect) {
+        for y in rect.top()..rect.bottom() {
+            for x in rect.left()..rect.right() {
+                self.canvas
+                    .try_set_at(x as u32, y as u32, Color::RGBA(0, 0, 0, 0));
+            }
+        }
+    }
+
+    pub fn advance_marching_ants(&mut self) {
+        self.marching_ants_phase = (self.marching_ants_phase + 1) % (2 * Self::ANT_LENGTH);
+    }
+
+    fn draw_marching_ants(&self, rect: Rect, screen_width: u32, screen_height: u32) {
+        let (offset_x, offset_y) = self.get_left_top_offset_i32(screen_width, screen_height);
+        let to_screen = |coord: i32, offset: i32| offset + self.scale.apply(coord as f64) as i32;
+        let left = to_screen(rect.left(), offset_x);
+        let top = to_screen(rect.top(), offset_y);
+        let right = to_screen(rect.right(), offset_x) - 1;
+        let bottom = to_screen(rect.bottom(), offset_y) - 1;
+
+        let mut perimeter = Vec::new();
+        perimeter.extend((left..right).map(|x| (x, top)));
+        perimeter.extend((top..bottom).map(|y| (right, y)));
+        perimeter.extend((left + 1..=right).rev().map(|x| (x, bottom)));
+        perimeter.extend((top + 1..=bottom).rev().map(|y| (left, y)));
+
+        let (black, white): (Vec<_>, Vec<_>) =
+            perimeter.into_iter().enumerate().partition(|(index, _)| {
+                (*index as u32 + self.marching_ants_phase) % (2 * Self::ANT_LENGTH)
+                    < Self::ANT_LENGTH
+            });
+        let to_points = |points: Vec<(usize, (i32, i32))>| {
+            points
+                .into_iter()
+                .map(|(_, point)| point.into())
+                .collect::<Vec<sdl2::rect::Point>>()
+        };
+
+        let mut sdl_canvas = self.canvas.sdl_canvas().borrow_mut();
+        sdl_canvas.set_draw_color(Color::BLACK);
+        sdl_canvas
+            .draw_points(to_points(black).as_slice())
+            .expect("Failed to draw the selection");
+        sdl_canvas.set_draw_color(Color::WHITE);
+        sdl_canvas
+            .draw_points(to_points(white).as_slice())
+            .expect("Failed to draw the selection");
+    }
+
+    const ANT_LENGTH: u32 = 4;
+
+    pub fn add_layer(&mut self) -> Result<(), LayerError> {
+        self.commit_selection();
+        if self.in_transaction {
+            return Err(LayerError::TransactionInProgress);
+        }
+        let layer = self.canvas.create_layer();
+        let index = self.canvas.active_layer_index() + 1;
+        self.apply_and_record(
+            Diff::InsertLayer {
+                index,
+                layer: Box::new(layer),
+            },
+            "Add layer",
+        );
+        Ok(())
+    }
+
+    pub fn remove_active_layer(&mut self) -> Result<(), LayerError> {
+        self.commit_selection();
+        if self.in_transaction {
+            return Err(LayerError::TransactionInProgress);
+        }
+        if self.canvas.layers().len() <= 1 {
+            return Err(LayerError::LastLayer);
+        }
+        let index = self.canvas.active_layer_index();
+        let layer = self.canvas.active_layer().clone();
+        self.apply_and_record(
+            Diff::RemoveLayer {
+                index,
+                layer: Box::new(layer),
+            },
+            "Remove layer",
+        );
+        Ok(())
+    }
+
+    /// Moves the active layer `delta` positions up (towards the top of the stack) or down.
+    pub fn move_active_layer(&mut self, delta: isize) -> Result<(), LayerError> {
+        self.commit_selection();
+        if self.in_transaction {
+            return Err(LayerError::TransactionInProgress);
+        }
+        let from = self.canvas.active_layer_index();
+        let to = self.relative_layer_index(delta)?;
+        self.apply_and_record(Diff::MoveLayer { from, to }, "Move layer");
+        Ok(())
+    }
+
+    pub fn select_layer_relative(&mut self, delta: isize) -> Result<(), LayerError> {
+        self.commit_selection();
+        if self.in_transaction {
+            return Err(LayerError::TransactionInProgress);
+        }
+        let index = self.relative_layer_index(delta)?;
+        self.canvas.set_active_layer(index).unwrap();
+        Ok(())
+    }
+
+    fn relative_layer_index(&self, delta: isize) -> Result<usize, LayerError> {
+        let index = self.canvas.active_layer_index() as isize + delta;
+        if (0..self.canvas.layers().len() as isize).contains(&index) {
+            Ok(index as usize)
+        } else {
+            Err(LayerError::NoSuchLayer)
+        }
+    }
+
+    fn apply_and_record(&mut self, diff: Diff, label: &str) {
+        self.canvas.apply_diff(&diff, DiffDirection::Normal);
+        self.history.record(diff, label.to_owned());
+    }
+
+    pub fn is_in_transaction(&self) -> bool {
+        self.in_transaction
+    }
+
+    /// Starts recording changes as a single history entry. A floating selection is committed
+    /// first, so that it gets an entry of its own. Transactions cannot be nested.
+    pub fn begin(&mut self) -> Result<(), TimeMachineError> {
+        self.commit_selection();
+        if self.in_transaction {
+            return Err(TimeMachineError::TransactionInProgress);
+        }
+        self.canvas.begin_snapshot();
         self.in_transaction = true;
+        self.transaction_label = self.tool_name.clone();
+        Ok(())
+    }
+
+    fn begin_labelled(&mut self, label: &str) -> Result<(), TimeMachineError> {
+        self.begin()?;
+        self.transaction_label = label.to_owned();
+        Ok(())
     }
 
     pub fn end(&mut self) {
-        let diff = self.canvas.compare_shadow_data(&self.shadow_data);
-        self.history.record(diff);
+        if let Some(diff) = self.canvas.finish_snapshot() {
+            let label = mem::take(&mut self.transaction_label);
+            self.history.record(diff, label);
+        }
         self.in_transaction = false;
     }
 
@@ -156,11 +634,27 @@ impl Editor {
             self.scale.unapply(w) + 2,
             self.scale.unapply(h) + 2,
         );
-        self.canvas.draw(
-            self.scale,
-            visible_rect,
-            self.get_left_top_offset_i32(w, h).into(),
+        let (left, top) = self.get_left_top_offset_i32(w, h);
+        self.canvas
+            .draw(self.scale, visible_rect, Point::new(left, top));
+        let image_rect = Rect::new(
+            left,
+            top,
+            self.scale.apply(self.canvas.width()).max(1),
+            self.scale.apply(self.canvas.height()).max(1),
         );
+        self.grid
+            .draw(
+                &mut self.canvas.sdl_canvas().borrow_mut(),
+                self.scale,
+                image_rect,
+                self.canvas.width(),
+                self.canvas.height(),
+            )
+            .expect("Failed to draw the grid");
+        if let Some(selection) = &self.selection {
+            self.draw_marching_ants(selection.rect, w, h);
+        }
     }
 
     pub fn get_left_top_offset_i32(&self, screen_width: u32, screen_height: u32) -> (i32, i32) {
@@ -184,6 +678,8 @@ impl Editor {
         Point::new(point.x - offset_x as f64, point.y - offset_y as f64)
             .map(|x| self.scale.unapply(x))
     }
+
+    pub const DEFAULT_HISTORY_BUDGET: usize = 256 * 1024 * 1024;
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -191,3 +687,76 @@ pub enum TimeMachineError {
     TransactionInProgress,
     AlreadyAtTimeEdge,
 }
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ZoomFit {
+    Fit,
+    Fill,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LayerError {
+    TransactionInProgress,
+    LastLayer,
+    NoSuchLayer,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::with_sdl_canvas;
+
+    #[test]
+    fn paste_then_stroke_undoes_both() {
+        with_sdl_canvas(|sdl_canvas| {
+            let red = Color::RGBA(255, 0, 0, 255);
+            let blue = Color::RGBA(0, 0, 255, 255);
+            let mut editor = Editor::new(8, 8, sdl_canvas);
+            editor.begin().unwrap();
+            editor.canvas_mut().try_set_at(0, 0, red);
+            editor.canvas_mut().try_set_at(1, 1, red);
+            editor.end();
+            editor.select(Some(Rect::new(0, 0, 2, 2)));
+            let buffer = editor.copy_selection().unwrap();
+            let before_paste = editor.canvas_mut().build_image();
+
+            editor.select(Some(Rect::new(4, 4, 2, 2)));
+            editor.paste(buffer).unwrap();
+            // A stroke started while the pasted pixels float commits them first.
+            editor.begin().unwrap();
+            editor.canvas_mut().try_set_at(7, 0, blue);
+            editor.end();
+            assert_eq!(editor.canvas().try_get_at(4, 4), Some(red));
+            assert_eq!(editor.canvas().try_get_at(7, 0), Some(blue));
+
+            editor.undo().unwrap();
+            assert_eq!(editor.canvas().try_get_at(4, 4), Some(red));
+            assert_eq!(editor.canvas().try_get_at(7, 0), Some(Color::WHITE));
+            editor.undo().unwrap();
+            assert_eq!(editor.canvas_mut().build_image(), before_paste);
+        });
+    }
+
+    #[test]
+    fn layer_properties_are_unsaved_changes() {
+        with_sdl_canvas(|sdl_canvas| {
+            let mut editor = Editor::new(4, 4, sdl_canvas);
+            assert!(!editor.has_unsaved_changes());
+            editor.update_active_layer(|layer| layer.visible = false);
+            assert!(editor.has_unsaved_changes());
+            editor.mark_saved();
+            assert!(!editor.has_unsaved_changes());
+        });
+    }
+
+    #[test]
+    fn transactions_do_not_nest() {
+        with_sdl_canvas(|sdl_canvas| {
+            let mut editor = Editor::new(4, 4, sdl_canvas);
+            editor.begin().unwrap();
+            assert_eq!(editor.begin(), Err(TimeMachineError::TransactionInProgress));
+            editor.end();
+            assert!(editor.begin().is_ok());
+        });
+    }
+}
@@ -107,27 +107,126 @@ impl Rectangle<f64> {
     }
 }
 
+/// The zoom level of the view. `Times(n)` magnifies every image pixel into an n×n square,
+/// while `Fraction` shrinks the image by a `numerator / denominator` ratio below one.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Scale {
     Times(u32),
+    Fraction { numerator: u32, denominator: u32 },
 }
 
 impl Scale {
-    pub fn apply<T: Mul<Output = T> + From<u32>>(self, num: T) -> T {
+    /// Zoom levels below 100%, from the smallest to the largest.
+    pub const FRACTIONS: [Scale; 5] = [
+        Scale::fraction(1, 8),
+        Scale::fraction(1, 4),
+        Scale::fraction(1, 3),
+        Scale::fraction(1, 2),
+        Scale::fraction(2, 3),
+    ];
+
+    /// The largest zoom level. Larger ones would overflow the on-screen size of big images.
+    pub const MAX: Scale = Scale::Times(64);
+
+    pub const fn fraction(numerator: u32, denominator: u32) -> Scale {
+        Scale::Fraction {
+            numerator,
+            denominator,
+        }
+    }
+
+    /// Picks the largest supported zoom level which does not exceed the factor.
+    pub fn at_most(factor: f64) -> Scale {
+        if factor >= Scale::MAX.factor() {
+            Scale::MAX
+        } else if factor >= 1.0 {
+            Scale::Times(factor.floor() as u32)
+        } else {
+            Scale::FRACTIONS
+                .iter()
+                .rev()
+                .copied()
+                .find(|s| s.factor() <= factor)
+                .unwrap_or(Scale::FRACTIONS[0])
+        }
+    }
+
+    /// Picks the smallest supported zoom level which is not less than the factor.
+    pub fn at_least(factor: f64) -> Scale {
+        if factor >= Scale::MAX.factor() {
+            Scale::MAX
+        } else if factor > 1.0 {
+            Scale::Times(factor.ceil() as u32)
+        } else {
+            Scale::FRACTIONS
+                .iter()
+                .copied()
+                .find(|s| s.factor() >= factor)
+                .unwrap_or(Scale::Times(1))
+        }
+    }
+
+    /// Picks the supported zoom level closest to the factor. Zoom levels are compared by their
+    /// ratio to the factor, the same way zooming in and out steps through them.
+    pub fn nearest(factor: f64) -> Scale {
+        let below = Scale::at_most(factor);
+        let above = Scale::at_least(factor);
+        if factor / below.factor() <= above.factor() / factor {
+            below
+        } else {
+            above
+        }
+    }
+
+    /// Converts a percentage to the nearest supported zoom level, which may differ from the
+    /// percentage, since magnification is only supported in whole multiples and reduction
+    /// only in the steps of `FRACTIONS`. Percentages above `MAX` are rejected.
+    pub fn from_percentage(percentage: f64) -> Option<Scale> {
+        if percentage > 0.0 && percentage <= Scale::MAX.factor() * 100.0 {
+            Some(Scale::nearest(percentage / 100.0))
+        } else {
+            None
+        }
+    }
+
+    pub fn factor(self) -> f64 {
+        self.apply(1.0)
+    }
+
+    pub fn apply<T: Mul<Output = T> + Div<Output = T> + From<u32>>(self, num: T) -> T {
         match self {
             Scale::Times(n) => num * n.into(),
+            Scale::Fraction {
+                numerator,
+                denominator,
+            } => num * numerator.into() / denominator.into(),
         }
     }
 
-    pub fn unapply<T: Div<Output = T> + From<u32>>(self, num: T) -> T {
+    pub fn unapply<T: Mul<Output = T> + Div<Output = T> + From<u32>>(self, num: T) -> T {
         match self {
             Scale::Times(n) => num / n.into(),
+            Scale::Fraction {
+                numerator,
+                denominator,
+            } => num * denominator.into() / numerator.into(),
         }
     }
 
     pub fn to_percentage_string(&self) -> String {
         match self {
             Scale::Times(n) => format!("{}00%", n),
+            Scale::Fraction {
+                numerator,
+                denominator,
+            } => {
+                let percentage = 100.0 * *numerator as f64 / *denominator as f64;
+                if (percentage * 2.0).fract() == 0.0 {
+                    format!("{}%", percentage)
+                } else {
+                    format!("{}%", percentage.floor())
+                }
+            }
         }
     }
 }
@@ -137,6 +236,42 @@ impl std::fmt::Display for Scale {
         match self {
             Scale::Times(0) => unreachable!(),
             Scale::Times(n) => write!(fmt, "{}x", n),
+            Scale::Fraction {
+                numerator,
+                denominator,
+            } => write!(fmt, "{}/{}x", numerator, denominator),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_from_percentage() {
+        assert_eq!(Scale::from_percentage(12.5), Some(Scale::fraction(1, 8)));
+        assert_eq!(Scale::from_percentage(33.0), Some(Scale::fraction(1, 3)));
+        assert_eq!(Scale::from_percentage(60.0), Some(Scale::fraction(2, 3)));
+        assert_eq!(Scale::from_percentage(5.0), Some(Scale::fraction(1, 8)));
+        assert_eq!(Scale::from_percentage(100.0), Some(Scale::Times(1)));
+        assert_eq!(Scale::from_percentage(140.0), Some(Scale::Times(1)));
+        assert_eq!(Scale::from_percentage(150.0), Some(Scale::Times(2)));
+        assert_eq!(Scale::from_percentage(420.0), Some(Scale::Times(4)));
+        assert_eq!(Scale::from_percentage(6400.0), Some(Scale::MAX));
+        assert_eq!(Scale::from_percentage(6401.0), None);
+        assert_eq!(Scale::from_percentage(4_000_000_000.0), None);
+        assert_eq!(Scale::from_percentage(0.0), None);
+        assert_eq!(Scale::from_percentage(-50.0), None);
+        assert_eq!(Scale::from_percentage(f64::NAN), None);
+        assert_eq!(Scale::from_percentage(f64::INFINITY), None);
+    }
+
+    #[test]
+    fn scale_limits() {
+        assert_eq!(Scale::at_most(1000.0), Scale::MAX);
+        assert_eq!(Scale::at_least(1000.0), Scale::MAX);
+        assert_eq!(Scale::at_least(2.5), Scale::Times(3));
+        assert_eq!(Scale::at_most(0.01), Scale::FRACTIONS[0]);
+    }
+}
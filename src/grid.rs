@@ -0,0 +1,127 @@
+use crate::geometry::Scale;
+use crate::SdlCanvas;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::BlendMode;
+
+/// Grid lines drawn over the canvas on screen. They never touch the image data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    pub pixel_grid: bool,
+    pub pixel_grid_color: Color,
+    /// The pixel grid is only shown at this zoom factor and above.
+    pub pixel_grid_min_zoom: f64,
+    pub tile_grid: bool,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tile_grid_color: Color,
+    /// The tile grid is only shown at this zoom factor and above.
+    pub tile_grid_min_zoom: f64,
+}
+
+impl Default for Grid {
+    fn default() -> Grid {
+        Grid {
+            pixel_grid: true,
+            pixel_grid_color: Color::RGBA(0, 0, 0, 48),
+            pixel_grid_min_zoom: 8.0,
+            tile_grid: true,
+            tile_width: 16,
+            tile_height: 16,
+            tile_grid_color: Color::RGBA(0, 120, 255, 128),
+            tile_grid_min_zoom: 2.0,
+        }
+    }
+}
+
+impl Grid {
+    /// Draws the grids which are enabled and visible at the given scale. `image_rect` is
+    /// the position of the whole image on the screen.
+    pub fn draw(
+        &self,
+        sdl_canvas: &mut SdlCanvas,
+        scale: Scale,
+        image_rect: Rect,
+        image_width: u32,
+        image_height: u32,
+    ) -> Result<(), String> {
+        let (screen_width, screen_height) = sdl_canvas.window().drawable_size();
+        let area = match image_rect.intersection(Rect::new(0, 0, screen_width, screen_height)) {
+            Some(area) => area,
+            None => return Ok(()),
+        };
+
+        sdl_canvas.set_blend_mode(BlendMode::Blend);
+        if self.pixel_grid && scale.factor() >= self.pixel_grid_min_zoom {
+            let lines = Self::lines(scale, image_rect, area, 1, 1, image_width, image_height);
+            sdl_canvas.set_draw_color(self.pixel_grid_color);
+            Self::draw_lines(sdl_canvas, &lines)?;
+        }
+        if self.tile_grid && scale.factor() >= self.tile_grid_min_zoom {
+            let lines = Self::lines(
+                scale,
+                image_rect,
+                area,
+                self.tile_width,
+                self.tile_height,
+                image_width,
+                image_height,
+            );
+            sdl_canvas.set_draw_color(self.tile_grid_color);
+            Self::draw_lines(sdl_canvas, &lines)?;
+        }
+        sdl_canvas.set_blend_mode(BlendMode::None);
+        Ok(())
+    }
+
+    fn lines(
+        scale: Scale,
+        image_rect: Rect,
+        area: Rect,
+        step_x: u32,
+        step_y: u32,
+        image_width: u32,
+        image_height: u32,
+    ) -> Vec<(Point, Point)> {
+        let to_screen = |coord: u32, origin: i32| origin + scale.apply(coord as f64) as i32;
+        let mut lines = Vec::new();
+        for x in (step_x..image_width).step_by(step_x as usize) {
+            let screen_x = to_screen(x, image_rect.left());
+            if (area.left()..area.right()).contains(&screen_x) {
+                lines.push((
+                    Point::new(screen_x, area.top()),
+                    Point::new(screen_x, area.bottom() - 1),
+                ));
+            }
+        }
+        for y in (step_y..image_height).step_by(step_y as usize) {
+            let screen_y = to_screen(y, image_rect.top());
+            if (area.top()..area.bottom()).contains(&screen_y) {
+                lines.push((
+                    Point::new(area.left(), screen_y),
+                    Point::new(area.right() - 1, screen_y),
+                ));
+            }
+        }
+        lines
+    }
+
+    fn draw_lines(sdl_canvas: &mut SdlCanvas, lines: &[(Point, Point)]) -> Result<(), String> {
+        for &(start, end) in lines {
+            sdl_canvas.draw_line(start, end)?;
+        }
+        Ok(())
+    }
+
+    /// Cycles through the common tile sizes.
+    pub fn next_tile_size(&mut self) {
+        let size = match self.tile_width {
+            8 => 16,
+            16 => 32,
+            32 => 64,
+            _ => 8,
+        };
+        self.tile_width = size;
+        self.tile_height = size;
+    }
+}
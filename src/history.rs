@@ -1,68 +1,546 @@
-use sdl2::pixels::Color;
+use crate::layer::{Layer, LayerId};
+use crate::patch::Patch;
+use std::collections::HashSet;
+use std::mem;
 
+/// Identifies a node of the undo tree. Nodes recorded later have greater identifiers.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct NodeId(usize);
+
+/// An undo tree. Recording a diff after undoing starts a new branch instead of discarding the
+/// undone diffs, so every state the image has been in stays reachable.
 pub struct History {
-    diffs: Vec<Diff>,
-    cursor: usize,
+    nodes: Vec<Option<Node>>,
+    root: NodeId,
+    current: NodeId,
+    checkpoints: Vec<Checkpoint>,
+    memory_budget: usize,
+    memory_usage: usize,
+}
+
+struct Node {
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// The child which `redo` moves to, i.e. the most recently visited one.
+    active_child: Option<NodeId>,
+    /// The diff leading from the parent to this node. The root has none.
+    diff: Option<Diff>,
+    /// Describes the action which has produced the diff, e.g. the name of a tool.
+    label: String,
 }
 
 impl History {
-    pub fn new() -> History {
+    /// Creates an empty history which forgets its oldest nodes once they take more than
+    /// `memory_budget` bytes. Abandoned branches are forgotten first, and the latest diff is
+    /// always kept.
+    pub fn new(memory_budget: usize) -> History {
+        let root = Node {
+            parent: None,
+            children: Vec::new(),
+            active_child: None,
+            diff: None,
+            label: "Original".to_owned(),
+        };
         History {
-            diffs: Vec::new(),
-            cursor: 0,
+            nodes: vec![Some(root)],
+            root: NodeId(0),
+            current: NodeId(0),
+            checkpoints: Vec::new(),
+            memory_budget,
+            memory_usage: 0,
+        }
+    }
+
+    /// The number of bytes taken by the recorded diffs.
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
+    /// Changes the memory budget, forgetting the oldest nodes right away if they no longer fit.
+    pub fn set_memory_budget(&mut self, memory_budget: usize) {
+        self.memory_budget = memory_budget;
+        self.evict();
+    }
+
+    pub fn current(&self) -> NodeId {
+        self.current
+    }
+
+    /// All nodes of the tree, parents before their children.
+    pub fn node_ids(&self) -> Vec<NodeId> {
+        (self.root.0..self.nodes.len())
+            .filter(|&index| self.nodes[index].is_some())
+            .map(NodeId)
+            .collect()
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn label(&self, id: NodeId) -> &str {
+        &self.node(id).label
+    }
+
+    pub fn diff(&self, id: NodeId) -> Option<&Diff> {
+        self.node(id).diff.as_ref()
+    }
+
+    /// The index of the node among its siblings, and the number of siblings (including the
+    /// node itself).
+    pub fn branch_position(&self, id: NodeId) -> (usize, usize) {
+        match self.node(id).parent {
+            Some(parent) => {
+                let siblings = &self.node(parent).children;
+                let index = siblings.iter().position(|&child| child == id).unwrap();
+                (index, siblings.len())
+            }
+            None => (0, 1),
+        }
+    }
+
+    /// The nodes from the root to the current one, followed by the ones `redo` would move
+    /// through.
+    pub fn active_branch(&self) -> Vec<NodeId> {
+        let mut branch = self.ancestors(self.current);
+        branch.reverse();
+        let mut id = self.current;
+        while let Some(child) = self.node(id).active_child {
+            branch.push(child);
+            id = child;
         }
+        branch
+    }
+
+    /// The child which `redo` moves to from the given node.
+    pub fn active_child(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).active_child
     }
 
-    #[inline]
-    fn consistency_check(&self) {
-        assert!(self.cursor <= self.diffs.len());
+    /// Makes `redo` move from the given node to the given one of its children.
+    pub fn set_active_child(&mut self, id: NodeId, child: NodeId) {
+        assert!(self.node(id).children.contains(&child));
+        self.node_mut(id).active_child = Some(child);
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Finds the most recently added checkpoint with the given name.
+    pub fn checkpoint_by_name(&self, name: &str) -> Option<&Checkpoint> {
+        self.checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.name == name)
+    }
+
+    /// Remembers the current node under the given name.
+    pub fn add_checkpoint(&mut self, name: String) {
+        self.checkpoints.push(Checkpoint {
+            name,
+            node: self.current,
+        });
     }
 
     pub fn undo(&mut self) -> Option<&Diff> {
-        let diff = if self.cursor == 0 {
-            None
+        let current = self.current;
+        let parent = self.node(current).parent?;
+        self.node_mut(parent).active_child = Some(current);
+        self.current = parent;
+        self.node(current).diff.as_ref()
+    }
+
+    pub fn redo(&mut self) -> Option<&Diff> {
+        let child = self.node(self.current).active_child?;
+        self.current = child;
+        self.node(child).diff.as_ref()
+    }
+
+    /// Returns the nodes whose diffs have to be undone, and then the ones which have to be
+    /// redone (in this order) to get from the current node to the target one.
+    pub fn path_to(&self, target: NodeId) -> (Vec<NodeId>, Vec<NodeId>) {
+        let from = self.ancestors(self.current);
+        let to = self.ancestors(target);
+        let shared: HashSet<NodeId> = from.iter().copied().collect();
+        let common = *to.iter().find(|id| shared.contains(id)).unwrap();
+        let undo = from.into_iter().take_while(|&id| id != common).collect();
+        let mut redo: Vec<_> = to.into_iter().take_while(|&id| id != common).collect();
+        redo.reverse();
+        (undo, redo)
+    }
+
+    /// Makes the given node current. The caller is responsible for applying the diffs along
+    /// the way, as returned by `path_to`.
+    pub fn set_current(&mut self, target: NodeId) {
+        let mut id = target;
+        while let Some(parent) = self.node(id).parent {
+            self.node_mut(parent).active_child = Some(id);
+            id = parent;
+        }
+        self.current = target;
+    }
+
+    /// The sibling which is `delta` positions away from the given node.
+    pub fn sibling(&self, id: NodeId, delta: isize) -> Option<NodeId> {
+        let parent = self.node(id).parent?;
+        let (index, _) = self.branch_position(id);
+        let index = index as isize + delta;
+        if index < 0 {
+            return None;
+        }
+        self.node(parent).children.get(index as usize).copied()
+    }
+
+    /// The node which was recorded `delta` nodes before or after the given one, regardless of
+    /// the branches they are on.
+    pub fn chronological_neighbour(&self, id: NodeId, delta: isize) -> Option<NodeId> {
+        let exists = |index: &usize| self.nodes[*index].is_some();
+        let skipped = delta.unsigned_abs().checked_sub(1)?;
+        let index = if delta < 0 {
+            (self.root.0..id.0).rev().filter(exists).nth(skipped)
         } else {
-            self.cursor -= 1;
-            Some(&self.diffs[self.cursor])
+            (id.0 + 1..self.nodes.len()).filter(exists).nth(skipped)
         };
+        index.map(NodeId)
+    }
 
-        self.consistency_check();
-        diff
+    pub fn record(&mut self, diff: Diff, label: String) {
+        let id = NodeId(self.nodes.len());
+        self.memory_usage += diff.memory_usage();
+        self.nodes.push(Some(Node {
+            parent: Some(self.current),
+            children: Vec::new(),
+            active_child: None,
+            diff: Some(diff),
+            label,
+        }));
+        let parent = self.node_mut(self.current);
+        parent.children.push(id);
+        parent.active_child = Some(id);
+        self.current = id;
+        self.evict();
     }
 
-    pub fn redo(&mut self) -> Option<&Diff> {
-        let diff = self.diffs.get(self.cursor);
-        if diff.is_some() {
-            self.cursor += 1;
+    fn evict(&mut self) {
+        while self.memory_usage > self.memory_budget {
+            if let Some(leaf) = self.leaf_to_forget() {
+                self.remove_leaf(leaf);
+            } else if matches!(self.node(self.current).parent, Some(parent) if parent != self.root)
+            {
+                self.advance_root();
+            } else {
+                break;
+            }
         }
-        self.consistency_check();
-        diff
+        let nodes = &self.nodes;
+        self.checkpoints
+            .retain(|checkpoint| nodes[checkpoint.node.0].is_some());
     }
 
-    pub fn record(&mut self, diff: Diff) {
-        self.consistency_check();
-        self.diffs.reserve(self.cursor + 1);
-        self.diffs.resize_with(self.cursor, || {
-            panic!("It is a bug to increase the size of the history vector")
-        });
-        self.diffs.push(diff);
-        self.cursor += 1;
-        self.consistency_check();
+    /// Picks the oldest leaf of an abandoned branch, i.e. one which is not on the active
+    /// branch. Once there are none, the end of what `redo` would move through is picked. The
+    /// current node is never picked.
+    fn leaf_to_forget(&self) -> Option<NodeId> {
+        let active: HashSet<NodeId> = self.active_branch().into_iter().collect();
+        let mut leaves =
+            (self.root.0..self.nodes.len())
+                .map(NodeId)
+                .filter(|&id| match &self.nodes[id.0] {
+                    Some(node) => node.children.is_empty() && id != self.current,
+                    None => false,
+                });
+        let first = leaves.next()?;
+        if !active.contains(&first) {
+            return Some(first);
+        }
+        // The active branch has at most one leaf other than the current node.
+        leaves.next().or(Some(first))
+    }
+
+    fn remove_leaf(&mut self, id: NodeId) {
+        let node = self.nodes[id.0].take().unwrap();
+        self.memory_usage -= node.diff.map_or(0, |diff| diff.memory_usage());
+        let parent = self.node_mut(node.parent.unwrap());
+        parent.children.retain(|&child| child != id);
+        if parent.active_child == Some(id) {
+            parent.active_child = parent.children.last().copied();
+        }
+    }
+
+    /// Forgets the root, making its only child the new root. Must only be called when the
+    /// tree has no other branches than the one leading to the current node.
+    fn advance_root(&mut self) {
+        let old_root = self.nodes[self.root.0].take().unwrap();
+        assert_eq!(old_root.children.len(), 1);
+        self.root = old_root.children[0];
+        let root = self.nodes[self.root.0].as_mut().unwrap();
+        root.parent = None;
+        if let Some(diff) = root.diff.take() {
+            self.memory_usage -= diff.memory_usage();
+        }
+    }
+
+    /// The given node followed by all of its ancestors up to the root.
+    fn ancestors(&self, id: NodeId) -> Vec<NodeId> {
+        let mut ancestors = vec![id];
+        let mut id = id;
+        while let Some(parent) = self.node(id).parent {
+            ancestors.push(parent);
+            id = parent;
+        }
+        ancestors
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        self.nodes[id.0]
+            .as_ref()
+            .expect("History refers to a forgotten node")
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.nodes[id.0]
+            .as_mut()
+            .expect("History refers to a forgotten node")
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub struct SparsePixelDelta {
-    pub index: usize,
-    pub before: Color,
-    pub after: Color,
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub name: String,
+    pub node: NodeId,
 }
 
 pub enum Diff {
-    Sparse(Vec<SparsePixelDelta>),
+    /// Changes to the pixels of a layer, split into tiles.
+    Tiled {
+        layer: LayerId,
+        tiles: Vec<Patch>,
+    },
+    InsertLayer {
+        index: usize,
+        layer: Box<Layer>,
+    },
+    RemoveLayer {
+        index: usize,
+        layer: Box<Layer>,
+    },
+    MoveLayer {
+        from: usize,
+        to: usize,
+    },
+}
+
+impl Diff {
+    /// The number of bytes taken by the diff, including its own size.
+    pub fn memory_usage(&self) -> usize {
+        let payload = match self {
+            Diff::Tiled { tiles, .. } => tiles.iter().map(Patch::memory_usage).sum(),
+            Diff::InsertLayer { layer, .. } | Diff::RemoveLayer { layer, .. } => {
+                mem::size_of::<Layer>() + layer.name.capacity() + layer.data.capacity()
+            }
+            Diff::MoveLayer { .. } => 0,
+        };
+        mem::size_of::<Diff>() + payload
+    }
 }
 
 pub enum DiffDirection {
     Normal,
     Reverse,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::rect::Rect;
+
+    /// The memory taken by `diff()`.
+    const DIFF_SIZE: usize = mem::size_of::<Diff>();
+
+    fn diff() -> Diff {
+        Diff::MoveLayer { from: 0, to: 0 }
+    }
+
+    fn record(history: &mut History, label: &str) -> NodeId {
+        history.record(diff(), label.to_owned());
+        history.current()
+    }
+
+    #[test]
+    fn branches() {
+        let mut history = History::new(usize::MAX);
+        let root = history.current();
+        let a = record(&mut history, "a");
+        let b = record(&mut history, "b");
+        assert!(history.undo().is_some());
+        assert_eq!(history.current(), a);
+        let c = record(&mut history, "c");
+        assert_eq!(history.branch_position(b), (0, 2));
+        assert_eq!(history.branch_position(c), (1, 2));
+        assert_eq!(history.active_branch(), vec![root, a, c]);
+
+        // Switch to the other branch, the way the previous-branch action does.
+        assert_eq!(history.sibling(c, -1), Some(b));
+        assert_eq!(history.sibling(c, 1), None);
+        assert_eq!(history.sibling(b, -1), None);
+        assert_eq!(history.path_to(b), (vec![c], vec![b]));
+        history.set_current(b);
+        assert_eq!(history.active_branch(), vec![root, a, b]);
+        history.undo();
+        history.undo();
+        assert_eq!(history.current(), root);
+        history.redo();
+        history.redo();
+        assert_eq!(history.current(), b);
+        assert!(history.redo().is_none());
+
+        // And back.
+        assert_eq!(history.path_to(c), (vec![b], vec![c]));
+        history.set_current(c);
+        assert_eq!(history.active_branch(), vec![root, a, c]);
+        assert_eq!(history.path_to(root), (vec![c, a], vec![]));
+        assert_eq!(history.path_to(c), (vec![], vec![]));
+    }
+
+    #[test]
+    fn chronological_steps() {
+        let mut history = History::new(usize::MAX);
+        let root = history.current();
+        let a = record(&mut history, "a");
+        let b = record(&mut history, "b");
+        history.undo();
+        let c = record(&mut history, "c");
+        history.set_current(a);
+        let d = record(&mut history, "d");
+
+        // Stepping back in time from d visits c and b, which are on other branches.
+        assert_eq!(history.chronological_neighbour(d, -1), Some(c));
+        assert_eq!(history.chronological_neighbour(c, -1), Some(b));
+        assert_eq!(history.chronological_neighbour(b, -1), Some(a));
+        assert_eq!(history.chronological_neighbour(a, -1), Some(root));
+        assert_eq!(history.chronological_neighbour(root, -1), None);
+        assert_eq!(history.chronological_neighbour(b, 2), Some(d));
+        assert_eq!(history.chronological_neighbour(d, 1), None);
+        assert_eq!(history.chronological_neighbour(b, 0), None);
+
+        history.set_current(b);
+        assert_eq!(history.active_branch(), vec![root, a, b]);
+        assert_eq!(history.path_to(c), (vec![b], vec![c]));
+    }
+
+    #[test]
+    fn evict_oldest_nodes() {
+        let mut history = History::new(2 * DIFF_SIZE);
+        history.add_checkpoint("original".to_owned());
+        let a = record(&mut history, "a");
+        history.add_checkpoint("a".to_owned());
+        let b = record(&mut history, "b");
+        assert_eq!(history.memory_usage(), 2 * DIFF_SIZE);
+        assert_eq!(history.checkpoints().len(), 2);
+
+        // The original state is forgotten, and a becomes the root, without its diff.
+        let c = record(&mut history, "c");
+        assert_eq!(history.memory_usage(), 2 * DIFF_SIZE);
+        assert_eq!(history.node_ids(), vec![a, b, c]);
+        assert_eq!(history.parent(a), None);
+        assert!(history.diff(a).is_none());
+        assert_eq!(history.checkpoints().len(), 1);
+        assert_eq!(history.checkpoints()[0].node, a);
+        history.undo();
+        history.undo();
+        assert_eq!(history.current(), a);
+        assert!(history.undo().is_none());
+
+        // The abandoned branch goes before any of the past.
+        history.redo();
+        let d = record(&mut history, "d");
+        assert_eq!(history.node_ids(), vec![a, b, d]);
+        assert_eq!(history.chronological_neighbour(d, -1), Some(b));
+    }
+
+    #[test]
+    fn keep_latest_diff() {
+        let mut history = History::new(0);
+        let a = record(&mut history, "a");
+        assert_eq!(history.memory_usage(), DIFF_SIZE);
+        assert!(history.diff(a).is_some());
+        let b = record(&mut history, "b");
+        assert_eq!(history.node_ids(), vec![a, b]);
+        assert_eq!(history.memory_usage(), DIFF_SIZE);
+    }
+
+    fn tiled(seed: u8) -> Diff {
+        let rect = Rect::new(0, 0, 8, 8);
+        let before = vec![0; 8 * 8 * 4];
+        let after: Vec<u8> = (0..before.len()).map(|i| (i as u8) ^ seed).collect();
+        Diff::Tiled {
+            layer: LayerId(0),
+            tiles: vec![Patch::between(
+                rect,
+                Patch::rows(&before, 8, rect),
+                Patch::rows(&after, 8, rect),
+            )],
+        }
+    }
+
+    #[test]
+    fn memory_usage() {
+        let diffs: Vec<Diff> = (1..=4).map(tiled).collect();
+        let sizes: Vec<usize> = diffs.iter().map(Diff::memory_usage).collect();
+        match &diffs[0] {
+            Diff::Tiled { tiles, .. } => {
+                assert_eq!(sizes[0], mem::size_of::<Diff>() + tiles[0].memory_usage())
+            }
+            _ => unreachable!(),
+        }
+
+        // Room for the last three diffs, but not for all four.
+        let budget = sizes[1] + sizes[2] + sizes[3];
+        let mut history = History::new(budget);
+        let mut ids = Vec::new();
+        for (index, diff) in diffs.into_iter().enumerate() {
+            history.record(diff, format!("Pencil {}", index));
+            ids.push(history.current());
+            let expected: usize = sizes[..=index].iter().sum();
+            if index < 3 {
+                assert_eq!(history.memory_usage(), expected);
+            }
+        }
+        // The original root is forgotten, and the oldest diff with it.
+        assert_eq!(history.memory_usage(), budget);
+        assert_eq!(history.node_ids(), ids);
+        assert!(history.diff(ids[0]).is_none());
+
+        history.undo();
+        history.set_memory_budget(0);
+        // The diff to redo goes first, then everything but the latest diff to undo.
+        assert_eq!(history.node_ids(), vec![ids[1], ids[2]]);
+        assert_eq!(history.memory_usage(), sizes[2]);
+    }
+
+    #[test]
+    fn evict_abandoned_branches_before_redo() {
+        let mut history = History::new(usize::MAX);
+        let a = record(&mut history, "a");
+        let b = record(&mut history, "b");
+        history.undo();
+        record(&mut history, "c");
+        history.add_checkpoint("c".to_owned());
+        // Go back to b, which abandons c, and undo it, so that b is left to redo.
+        history.set_current(b);
+        history.undo();
+        assert_eq!(history.active_branch(), vec![history.root, a, b]);
+
+        history.set_memory_budget(2 * DIFF_SIZE);
+        assert_eq!(history.node_ids(), vec![history.root, a, b]);
+        assert!(history.checkpoints().is_empty());
+        assert!(history.redo().is_some());
+        assert_eq!(history.current(), b);
+
+        history.undo();
+        history.set_memory_budget(DIFF_SIZE);
+        assert_eq!(history.node_ids(), vec![history.root, a]);
+        assert_eq!(history.active_child(a), None);
+    }
+}
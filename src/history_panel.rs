@@ -0,0 +1,139 @@
+use crate::history::{History, NodeId};
+use crate::text::Align;
+use crate::{SdlApp, SdlError};
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+
+/// A panel along the right edge of the window which lists the active branch of the undo
+/// history. Clicking a row jumps to the state right after the corresponding action.
+pub struct HistoryPanel {
+    visible: bool,
+    first_row: usize,
+    rows: Vec<NodeId>,
+}
+
+impl HistoryPanel {
+    pub fn new() -> HistoryPanel {
+        HistoryPanel {
+            visible: false,
+            first_row: 0,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn draw(&mut self, sdl_app: &mut SdlApp, history: &History) -> Result<(), SdlError> {
+        if !self.visible {
+            return Ok(());
+        }
+        let mut canvas = sdl_app.sdl_canvas.borrow_mut();
+        let text_renderer = &mut sdl_app.text;
+
+        let (screen_width, screen_height) = canvas.window().drawable_size();
+        let panel_rect = Self::panel_rect(screen_width, screen_height);
+        canvas.set_draw_color(Self::BACKGROUND_COLOR);
+        canvas.fill_rect(panel_rect)?;
+
+        self.rows = history.active_branch();
+        let current_row = self
+            .rows
+            .iter()
+            .position(|&id| id == history.current())
+            .unwrap();
+        let visible_rows = (screen_height / Self::ROW_HEIGHT).max(1) as usize;
+        self.first_row = current_row
+            .saturating_sub(visible_rows / 2)
+            .min(self.rows.len().saturating_sub(visible_rows));
+
+        for row in self.first_row..self.rows.len().min(self.first_row + visible_rows) {
+            let id = self.rows[row];
+            let row_rect = self.row_rect(row, screen_width, screen_height);
+            let text_color = if row == current_row {
+                canvas.set_draw_color(Self::SELECTED_COLOR);
+                canvas.fill_rect(row_rect)?;
+                Color::WHITE
+            } else if row > current_row {
+                Self::UNDONE_COLOR
+            } else {
+                Color::BLACK
+            };
+
+            let mut text = history.label(id).to_owned();
+            let (branch, branch_count) = history.branch_position(id);
+            if branch_count > 1 {
+                text += &format!(" ({}/{})", branch + 1, branch_count);
+            }
+            for checkpoint in history.checkpoints() {
+                if checkpoint.node == id {
+                    text += &format!(" [{}]", checkpoint.name);
+                }
+            }
+
+            text_renderer.draw_in(
+                &mut canvas,
+                &text,
+                Self::FONT_SIZE,
+                text_color,
+                row_rect,
+                Align::Left(Self::PADDING),
+            )?;
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_line(
+            Point::new(panel_rect.left(), 0),
+            Point::new(panel_rect.left(), screen_height as i32),
+        )?;
+        Ok(())
+    }
+
+    pub fn contains(&self, x: i32, y: i32, screen_width: u32, screen_height: u32) -> bool {
+        self.visible && Self::panel_rect(screen_width, screen_height).contains_point((x, y))
+    }
+
+    /// Returns the history node whose row is located at the given point.
+    pub fn hit_test(
+        &self,
+        x: i32,
+        y: i32,
+        screen_width: u32,
+        screen_height: u32,
+    ) -> Option<NodeId> {
+        (self.first_row..self.rows.len())
+            .find(|&row| {
+                self.row_rect(row, screen_width, screen_height)
+                    .contains_point((x, y))
+            })
+            .map(|row| self.rows[row])
+    }
+
+    fn panel_rect(screen_width: u32, screen_height: u32) -> Rect {
+        Rect::new(
+            screen_width as i32 - Self::WIDTH as i32,
+            0,
+            Self::WIDTH,
+            screen_height.max(1),
+        )
+    }
+
+    fn row_rect(&self, row: usize, screen_width: u32, screen_height: u32) -> Rect {
+        let panel_rect = Self::panel_rect(screen_width, screen_height);
+        Rect::new(
+            panel_rect.left(),
+            ((row - self.first_row) as u32 * Self::ROW_HEIGHT) as i32,
+            Self::WIDTH,
+            Self::ROW_HEIGHT,
+        )
+    }
+
+    const WIDTH: u32 = 200;
+    const ROW_HEIGHT: u32 = 24;
+    const PADDING: i32 = 6;
+    const FONT_SIZE: u16 = 14;
+    const BACKGROUND_COLOR: Color = Color::RGB(230, 230, 230);
+    const SELECTED_COLOR: Color = Color::RGB(60, 110, 200);
+    const UNDONE_COLOR: Color = Color::RGB(140, 140, 140);
+}
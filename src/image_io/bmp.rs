@@ -0,0 +1,219 @@
+use super::{checked_data_size, invalid, unsupported, RgbaImage};
+use std::error::Error;
+
+/// Decodes uncompressed 24-bit and 32-bit BMP images, either bottom-up or top-down.
+pub fn decode(data: &[u8]) -> Result<RgbaImage, Box<dyn Error>> {
+    if data.len() < FILE_HEADER_SIZE + 40 || &data[..2] != b"BM" {
+        return Err(invalid("not a BMP file"));
+    }
+    let pixel_offset = read_u32(data, 10) as usize;
+    let header_size = read_u32(data, 14) as usize;
+    if header_size < 40 {
+        return Err(unsupported("BMP core headers are not supported"));
+    }
+    let width = read_i32(data, 18);
+    let height = read_i32(data, 22);
+    let bits_per_pixel = read_u16(data, 28);
+    let compression = read_u32(data, 30);
+    if width < 0 {
+        return Err(invalid("negative width"));
+    }
+
+    // Positive heights mean that rows are stored from the bottom to the top.
+    let bottom_up = height > 0;
+    let (width, height) = (width as u32, height.unsigned_abs());
+    let size = checked_data_size(width, height)?;
+
+    // Channel masks for 32-bit images. Without bit fields, the alpha channel is unused.
+    let masks = match (bits_per_pixel, compression) {
+        (24, COMPRESSION_RGB) => None,
+        (32, COMPRESSION_RGB) => Some([0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0]),
+        (32, COMPRESSION_BITFIELDS) | (32, COMPRESSION_ALPHA_BITFIELDS) => {
+            // The masks either follow a 40-byte header or are a part of a longer one.
+            let masks_offset = FILE_HEADER_SIZE + 40;
+            if data.len() < masks_offset + 16 {
+                return Err(invalid("truncated channel masks"));
+            }
+            let alpha_mask = if header_size >= 56 || compression == COMPRESSION_ALPHA_BITFIELDS {
+                read_u32(data, masks_offset + 12)
+            } else {
+                0
+            };
+            Some([
+                read_u32(data, masks_offset),
+                read_u32(data, masks_offset + 4),
+                read_u32(data, masks_offset + 8),
+                alpha_mask,
+            ])
+        }
+        _ => {
+            return Err(unsupported(&format!(
+                "BMP with {} bits per pixel and compression {}",
+                bits_per_pixel, compression
+            )))
+        }
+    };
+
+    let bytes_per_pixel = bits_per_pixel as usize / 8;
+    let stride = row_stride(width, bits_per_pixel);
+    let pixels_end = pixel_offset
+        .checked_add(stride * height as usize)
+        .ok_or_else(|| invalid("image is too large"))?;
+    if data.len() < pixels_end {
+        return Err(invalid("truncated pixel data"));
+    }
+
+    let mut result = Vec::with_capacity(size);
+    for y in 0..height as usize {
+        let stored_row = if bottom_up {
+            height as usize - 1 - y
+        } else {
+            y
+        };
+        let row_start = pixel_offset + stored_row * stride;
+        let row = &data[row_start..row_start + width as usize * bytes_per_pixel];
+        for pixel in row.chunks_exact(bytes_per_pixel) {
+            match masks {
+                None => result.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]),
+                Some([red, green, blue, alpha]) => {
+                    let value = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                    result.extend_from_slice(&[
+                        extract(value, red),
+                        extract(value, green),
+                        extract(value, blue),
+                        if alpha == 0 {
+                            255
+                        } else {
+                            extract(value, alpha)
+                        },
+                    ]);
+                }
+            }
+        }
+    }
+
+    Ok(RgbaImage {
+        width,
+        height,
+        data: result,
+    })
+}
+
+/// Encodes an image as a bottom-up BMP. Images with transparent pixels are stored with 32
+/// bits per pixel and an alpha mask, opaque ones with 24 bits per pixel.
+pub fn encode(image: &RgbaImage) -> Vec<u8> {
+    let has_alpha = image.data.chunks_exact(4).any(|pixel| pixel[3] != 255);
+    let bits_per_pixel: u16 = if has_alpha { 32 } else { 24 };
+    let info_header_size: u32 = if has_alpha { 108 } else { 40 };
+    let stride = row_stride(image.width, bits_per_pixel);
+    let pixel_offset = FILE_HEADER_SIZE as u32 + info_header_size;
+    let image_size = (stride * image.height as usize) as u32;
+
+    let mut buf = Vec::with_capacity(pixel_offset as usize + image_size as usize);
+    buf.extend_from_slice(b"BM");
+    buf.extend_from_slice(&(pixel_offset + image_size).to_le_bytes());
+    buf.extend_from_slice(&[0; 4]);
+    buf.extend_from_slice(&pixel_offset.to_le_bytes());
+
+    buf.extend_from_slice(&info_header_size.to_le_bytes());
+    buf.extend_from_slice(&(image.width as i32).to_le_bytes());
+    buf.extend_from_slice(&(image.height as i32).to_le_bytes());
+    buf.extend_from_slice(&1u16.to_le_bytes());
+    buf.extend_from_slice(&bits_per_pixel.to_le_bytes());
+    let compression = if has_alpha {
+        COMPRESSION_BITFIELDS
+    } else {
+        COMPRESSION_RGB
+    };
+    buf.extend_from_slice(&compression.to_le_bytes());
+    buf.extend_from_slice(&image_size.to_le_bytes());
+    // 72 DPI, no palette.
+    buf.extend_from_slice(&2835u32.to_le_bytes());
+    buf.extend_from_slice(&2835u32.to_le_bytes());
+    buf.extend_from_slice(&[0; 8]);
+    if has_alpha {
+        for mask in &[0x00ff_0000u32, 0x0000_ff00, 0x0000_00ff, 0xff00_0000] {
+            buf.extend_from_slice(&mask.to_le_bytes());
+        }
+        // The sRGB color space, with unused endpoints and gamma values.
+        buf.extend_from_slice(b"BGRs");
+        buf.extend_from_slice(&[0; 48]);
+    }
+
+    let row_len = image.width as usize * 4;
+    for row in image.data.chunks_exact(row_len).rev() {
+        let row_start = buf.len();
+        for pixel in row.chunks_exact(4) {
+            buf.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+            if has_alpha {
+                buf.push(pixel[3]);
+            }
+        }
+        buf.resize(row_start + stride, 0);
+    }
+    buf
+}
+
+/// Rows are padded to a multiple of four bytes.
+fn row_stride(width: u32, bits_per_pixel: u16) -> usize {
+    (width as usize * bits_per_pixel as usize / 8).div_ceil(4) * 4
+}
+
+fn extract(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    // Masks may be up to 32 bits wide, so the scaling must not overflow 32 bits.
+    let shifted = u64::from((value & mask) >> mask.trailing_zeros());
+    let max = u64::from(mask >> mask.trailing_zeros());
+    (shifted * 255 / max) as u8
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_i32(data: &[u8], offset: usize) -> i32 {
+    read_u32(data, offset) as i32
+}
+
+const FILE_HEADER_SIZE: usize = 14;
+const COMPRESSION_RGB: u32 = 0;
+const COMPRESSION_BITFIELDS: u32 = 3;
+const COMPRESSION_ALPHA_BITFIELDS: u32 = 6;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_io::test_image;
+
+    #[test]
+    fn round_trip_24_bit() {
+        let image = test_image(13, 7, true);
+        let encoded = encode(&image);
+        assert_eq!(read_u16(&encoded, 28), 24);
+        assert_eq!(decode(&encoded).unwrap(), image);
+    }
+
+    #[test]
+    fn round_trip_32_bit() {
+        let image = test_image(13, 7, false);
+        let encoded = encode(&image);
+        assert_eq!(read_u16(&encoded, 28), 32);
+        assert_eq!(decode(&encoded).unwrap(), image);
+    }
+
+    #[test]
+    fn extract_wide_masks() {
+        assert_eq!(extract(0xFFFF_FFFF, 0xFFFF_FFFF), 255);
+        assert_eq!(extract(0x8000_0000, 0xFFFF_FFFF), 127);
+        assert_eq!(extract(0x0000_FF00, 0x0000_FF00), 255);
+        assert_eq!(extract(0x1234_5678, 0), 0);
+    }
+}
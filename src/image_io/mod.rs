@@ -0,0 +1,266 @@
+mod bmp;
+mod png;
+mod pnm;
+mod qoi;
+mod tga;
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// A decoded image in the same RGBA layout as the one produced by `Canvas::build_image`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnsupportedFormatError(String);
+
+impl fmt::Display for UnsupportedFormatError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(formatter, "Unsupported image format: {}", self.0)
+    }
+}
+
+impl Error for UnsupportedFormatError {}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InvalidImageError(String);
+
+impl fmt::Display for InvalidImageError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(formatter, "Invalid image data: {}", self.0)
+    }
+}
+
+impl Error for InvalidImageError {}
+
+fn invalid(message: &str) -> Box<dyn Error> {
+    Box::new(InvalidImageError(message.to_owned()))
+}
+
+fn unsupported(message: &str) -> Box<dyn Error> {
+    Box::new(UnsupportedFormatError(message.to_owned()))
+}
+
+/// Checks the dimensions read from an image header, or of an image about to be created, and
+/// returns the size of the decoded data.
+pub fn checked_data_size(width: u32, height: u32) -> Result<usize, Box<dyn Error>> {
+    if width == 0 || height == 0 {
+        return Err(invalid("empty image"));
+    }
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|area| area.checked_mul(4))
+        .filter(|&size| size <= MAX_DATA_SIZE)
+        .ok_or_else(|| invalid("image is too large"))
+}
+
+const MAX_DATA_SIZE: usize = 1 << 31;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Format {
+    Png,
+    Bmp,
+    Tga,
+    /// Color PNM, i.e. PPM.
+    Ppm,
+    /// Grayscale PNM, i.e. PGM.
+    Pgm,
+    Qoi,
+}
+
+impl Format {
+    pub const ALL: [Format; 6] = [
+        Format::Png,
+        Format::Bmp,
+        Format::Tga,
+        Format::Ppm,
+        Format::Pgm,
+        Format::Qoi,
+    ];
+
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Format::Png => &["png"],
+            Format::Bmp => &["bmp"],
+            Format::Tga => &["tga"],
+            Format::Ppm => &["ppm", "pnm"],
+            Format::Pgm => &["pgm"],
+            Format::Qoi => &["qoi"],
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|format| format.extensions().contains(&extension.as_str()))
+    }
+
+    /// Picks the format to save a file in. Files without an extension are saved as PNG, as
+    /// they always used to be.
+    pub fn for_saving(path: &Path) -> Result<Format, Box<dyn Error>> {
+        match path.extension() {
+            None => Ok(Format::Png),
+            Some(extension) => Self::from_path(path)
+                .ok_or_else(|| unsupported(&format!("unknown file extension {:?}", extension))),
+        }
+    }
+
+    /// Whether the format has a plain variant: plain text for PNM, and uncompressed for TGA.
+    pub fn has_plain_variant(self) -> bool {
+        matches!(self, Format::Ppm | Format::Pgm | Format::Tga)
+    }
+}
+
+/// Loads an image, picking the format from the file extension.
+pub fn load(path: &Path) -> Result<RgbaImage, Box<dyn Error>> {
+    let format = Format::from_path(path)
+        .ok_or_else(|| unsupported(&format!("unknown file extension of {}", path.display())))?;
+    let data = fs::read(path)?;
+    decode(format, &data)
+}
+
+/// Saves an image in the given format. `plain` selects the plain variant for formats which
+/// have one, see `Format::has_plain_variant`.
+pub fn save(
+    path: &Path,
+    image: &RgbaImage,
+    format: Format,
+    plain: bool,
+) -> Result<(), Box<dyn Error>> {
+    fs::write(path, encode(format, image, plain)?)?;
+    Ok(())
+}
+
+pub fn decode(format: Format, data: &[u8]) -> Result<RgbaImage, Box<dyn Error>> {
+    match format {
+        Format::Png => png::decode(data),
+        Format::Bmp => bmp::decode(data),
+        Format::Tga => tga::decode(data),
+        // PNM files carry their own subtype, so both extensions are decoded the same way.
+        Format::Ppm | Format::Pgm => pnm::decode(data),
+        Format::Qoi => qoi::decode(data),
+    }
+}
+
+pub fn encode(format: Format, image: &RgbaImage, plain: bool) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(match format {
+        Format::Png => png::encode(image)?,
+        Format::Bmp => bmp::encode(image),
+        Format::Tga => tga::encode(image, !plain)?,
+        Format::Ppm => pnm::encode(image, pnm::Kind::Color, plain),
+        Format::Pgm => pnm::encode(image, pnm::Kind::Gray, plain),
+        Format::Qoi => qoi::encode(image),
+    })
+}
+
+#[cfg(test)]
+fn test_image(width: u32, height: u32, opaque: bool) -> RgbaImage {
+    let mut data = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if y % 3 == 0 {
+                // Long runs of the same color exercise run-length encoding.
+                let alpha = if opaque { 255 } else { (y * 7) as u8 };
+                data.extend_from_slice(&[10, 20, 30, alpha]);
+            } else {
+                let alpha = if opaque { 255 } else { (x * 40 + y * 7) as u8 };
+                data.extend_from_slice(&[(x * 13) as u8, (y * 29) as u8, (x ^ y) as u8, alpha]);
+            }
+        }
+    }
+    RgbaImage {
+        width,
+        height,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Canvas;
+    use crate::layer::{Layer, LayerId};
+    use crate::test_support::with_sdl_canvas;
+
+    /// Composites two layers, the top one half transparent, into an image. Opaque layers give
+    /// an opaque image, and gray layers a gray one.
+    fn canvas_image(opaque: bool, gray: bool) -> RgbaImage {
+        let (width, height) = (9, 5);
+        with_sdl_canvas(move |sdl_canvas| {
+            let layer = |id, seed: u32| {
+                let mut data = Vec::new();
+                for y in 0..height {
+                    for x in 0..width {
+                        let value = x * 31 + y * 17 + seed;
+                        let (green, red) = if gray {
+                            (value, value)
+                        } else {
+                            (value * 3, value * 7)
+                        };
+                        let alpha = if opaque { 255 } else { x * 32 + y * 8 + seed };
+                        data.extend_from_slice(&[value as u8, green as u8, red as u8, alpha as u8]);
+                    }
+                }
+                Layer::new(LayerId(id), format!("Layer {}", id), data)
+            };
+            let mut top = layer(1, 100);
+            top.opacity = 0.6;
+            let mut canvas = Canvas::new(width, height, sdl_canvas);
+            canvas.load_layers(width, height, vec![layer(0, 0), top], 1);
+            RgbaImage {
+                width,
+                height,
+                data: canvas.build_image(),
+            }
+        })
+    }
+
+    fn assert_round_trip(format: Format, image: &RgbaImage) {
+        for &plain in [false, true].iter() {
+            let encoded = encode(format, image, plain).unwrap();
+            assert_eq!(
+                &decode(format, &encoded).unwrap(),
+                image,
+                "{:?}, plain: {}",
+                format,
+                plain
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip_canvas_image() {
+        let image = canvas_image(false, false);
+        assert!(image
+            .data
+            .chunks_exact(4)
+            .any(|pixel| pixel[3] != 0 && pixel[3] != 255));
+        for &format in [Format::Png, Format::Bmp, Format::Tga, Format::Qoi].iter() {
+            assert_round_trip(format, &image);
+        }
+
+        // PNM has no alpha channel, and PGM no color.
+        let opaque = canvas_image(true, false);
+        assert!(opaque.data.chunks_exact(4).all(|pixel| pixel[3] == 255));
+        assert_round_trip(Format::Ppm, &opaque);
+        assert_round_trip(Format::Pgm, &canvas_image(true, true));
+    }
+
+    #[test]
+    fn plain_tga_is_uncompressed() {
+        let image = test_image(13, 7, true);
+        let compressed = encode(Format::Tga, &image, false).unwrap();
+        let plain = encode(Format::Tga, &image, true).unwrap();
+        assert!(compressed.len() < plain.len());
+        assert_eq!((compressed[2], plain[2]), (10, 2));
+        assert_eq!(decode(Format::Tga, &plain).unwrap(), image);
+    }
+}
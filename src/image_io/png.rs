@@ -0,0 +1,69 @@
+use super::{unsupported, RgbaImage};
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+use std::error::Error;
+
+pub fn decode(data: &[u8]) -> Result<RgbaImage, Box<dyn Error>> {
+    let mut decoder = Decoder::new(data);
+
+    // Expand palettes, low bit depths and tRNS chunks into plain 8-bit channels, so that
+    // we only have to deal with four color types below.
+    decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info()?;
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf)?;
+
+    if info.bit_depth != BitDepth::Eight {
+        return Err(unsupported(&format!(
+            "unexpected bit depth after expansion: {:?}",
+            info.bit_depth
+        )));
+    }
+
+    let channels = match info.color_type {
+        ColorType::Grayscale => 1,
+        ColorType::GrayscaleAlpha => 2,
+        ColorType::RGB => 3,
+        ColorType::RGBA => 4,
+        ColorType::Indexed => return Err(unsupported("palette was not expanded")),
+    };
+
+    let mut data = Vec::with_capacity(info.width as usize * info.height as usize * 4);
+    for row in buf.chunks(info.line_size).take(info.height as usize) {
+        for pixel in row.chunks(channels).take(info.width as usize) {
+            let rgba = match *pixel {
+                [l] => [l, l, l, 255],
+                [l, a] => [l, l, l, a],
+                [r, g, b] => [r, g, b, 255],
+                [r, g, b, a] => [r, g, b, a],
+                _ => unreachable!(),
+            };
+            data.extend_from_slice(&rgba);
+        }
+    }
+
+    Ok(RgbaImage {
+        width: info.width,
+        height: info.height,
+        data,
+    })
+}
+
+pub fn encode(image: &RgbaImage) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut buf = Vec::new();
+    let mut encoder = Encoder::new(&mut buf, image.width, image.height);
+    encoder.set_color(ColorType::RGBA);
+    encoder.write_header()?.write_image_data(&image.data)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_io::test_image;
+
+    #[test]
+    fn round_trip() {
+        let image = test_image(13, 7, false);
+        assert_eq!(decode(&encode(&image).unwrap()).unwrap(), image);
+    }
+}
@@ -0,0 +1,213 @@
+use super::{checked_data_size, invalid, unsupported, RgbaImage};
+use std::error::Error;
+use std::io::Write;
+
+/// The kind of a PNM image which is written.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Kind {
+    /// PPM, with a red, green and blue sample per pixel.
+    Color,
+    /// PGM, with a single luma sample per pixel.
+    Gray,
+}
+
+/// Decodes binary and plain PPM and PGM images. PNM has no alpha channel, so the decoded
+/// images are opaque.
+pub fn decode(data: &[u8]) -> Result<RgbaImage, Box<dyn Error>> {
+    let mut tokens = Tokens { data, offset: 0 };
+    let (kind, plain) = match tokens.next().ok_or_else(|| invalid("not a PNM file"))? {
+        b"P2" => (Kind::Gray, true),
+        b"P3" => (Kind::Color, true),
+        b"P5" => (Kind::Gray, false),
+        b"P6" => (Kind::Color, false),
+        b"P1" | b"P4" => return Err(unsupported("PBM bitmaps are not supported")),
+        b"P7" => return Err(unsupported("PAM images are not supported")),
+        _ => return Err(invalid("not a PNM file")),
+    };
+    let width = tokens.number()?;
+    let height = tokens.number()?;
+    let max_value = tokens.number()?;
+    if max_value == 0 || max_value > u16::MAX as u32 {
+        return Err(invalid("sample maximum out of range"));
+    }
+    let size = checked_data_size(width, height)?;
+    let samples_per_pixel = match kind {
+        Kind::Color => 3,
+        Kind::Gray => 1,
+    };
+    let sample_count = width as usize * height as usize * samples_per_pixel;
+
+    let samples: Vec<u32> = if plain {
+        (0..sample_count)
+            .map(|_| tokens.number())
+            .collect::<Result<_, _>>()?
+    } else {
+        // A single whitespace character separates the header from the raster.
+        let start = tokens.offset + 1;
+        let bytes_per_sample = if max_value > 255 { 2 } else { 1 };
+        let raster = data
+            .get(start..start + sample_count * bytes_per_sample)
+            .ok_or_else(|| invalid("truncated pixel data"))?;
+        if bytes_per_sample == 2 {
+            raster
+                .chunks_exact(2)
+                .map(|sample| u16::from_be_bytes([sample[0], sample[1]]) as u32)
+                .collect()
+        } else {
+            raster.iter().map(|&sample| sample as u32).collect()
+        }
+    };
+    if samples.iter().any(|&sample| sample > max_value) {
+        return Err(invalid("sample exceeds the maximum value"));
+    }
+
+    let scale = |sample: u32| ((sample * 255 + max_value / 2) / max_value) as u8;
+    let mut result = Vec::with_capacity(size);
+    for pixel in samples.chunks_exact(samples_per_pixel) {
+        match *pixel {
+            [l] => result.extend_from_slice(&[scale(l), scale(l), scale(l), 255]),
+            [r, g, b] => result.extend_from_slice(&[scale(r), scale(g), scale(b), 255]),
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(RgbaImage {
+        width,
+        height,
+        data: result,
+    })
+}
+
+/// Encodes an image as an 8-bit PPM or PGM, dropping the alpha channel. Grayscale images
+/// store the Rec. 601 luma of each pixel.
+pub fn encode(image: &RgbaImage, kind: Kind, plain: bool) -> Vec<u8> {
+    let magic = match (kind, plain) {
+        (Kind::Gray, true) => "P2",
+        (Kind::Color, true) => "P3",
+        (Kind::Gray, false) => "P5",
+        (Kind::Color, false) => "P6",
+    };
+    let mut buf = format!("{}\n{} {}\n255\n", magic, image.width, image.height).into_bytes();
+
+    let samples: Vec<u8> = match kind {
+        Kind::Color => image
+            .data
+            .chunks_exact(4)
+            .flat_map(|pixel| pixel[..3].to_vec())
+            .collect(),
+        Kind::Gray => image.data.chunks_exact(4).map(luma).collect(),
+    };
+    if !plain {
+        buf.extend_from_slice(&samples);
+        return buf;
+    }
+
+    // Plain files should not have lines longer than 70 characters, so each image row is
+    // split into lines of at most 16 samples.
+    let row_len = image.width as usize
+        * match kind {
+            Kind::Color => 3,
+            Kind::Gray => 1,
+        };
+    for row in samples.chunks_exact(row_len) {
+        for line in row.chunks(PLAIN_SAMPLES_PER_LINE) {
+            let line: Vec<String> = line.iter().map(u8::to_string).collect();
+            writeln!(buf, "{}", line.join(" ")).unwrap();
+        }
+    }
+    buf
+}
+
+fn luma(pixel: &[u8]) -> u8 {
+    let weighted = 299 * pixel[0] as u32 + 587 * pixel[1] as u32 + 114 * pixel[2] as u32;
+    ((weighted + 500) / 1000) as u8
+}
+
+/// Splits a PNM header (or a whole plain PNM file) into whitespace-separated tokens,
+/// skipping comments.
+struct Tokens<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn next(&mut self) -> Option<&'a [u8]> {
+        loop {
+            match self.data.get(self.offset)? {
+                b'#' => {
+                    while !matches!(self.data.get(self.offset), None | Some(b'\n') | Some(b'\r')) {
+                        self.offset += 1;
+                    }
+                }
+                byte if byte.is_ascii_whitespace() => self.offset += 1,
+                _ => break,
+            }
+        }
+        let start = self.offset;
+        while matches!(self.data.get(self.offset), Some(byte) if !byte.is_ascii_whitespace()) {
+            self.offset += 1;
+        }
+        Some(&self.data[start..self.offset])
+    }
+
+    fn number(&mut self) -> Result<u32, Box<dyn Error>> {
+        let token = self
+            .next()
+            .ok_or_else(|| invalid("unexpected end of file"))?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| invalid("expected a number"))
+    }
+}
+
+const PLAIN_SAMPLES_PER_LINE: usize = 16;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_io::test_image;
+
+    fn gray_image() -> RgbaImage {
+        let mut image = test_image(13, 7, true);
+        for pixel in image.data.chunks_exact_mut(4) {
+            let l = luma(pixel);
+            pixel[..3].copy_from_slice(&[l, l, l]);
+        }
+        image
+    }
+
+    #[test]
+    fn round_trip_binary_ppm() {
+        let image = test_image(13, 7, true);
+        assert_eq!(decode(&encode(&image, Kind::Color, false)).unwrap(), image);
+    }
+
+    #[test]
+    fn round_trip_plain_ppm() {
+        let image = test_image(13, 7, true);
+        let encoded = encode(&image, Kind::Color, true);
+        assert!(encoded
+            .split(|&byte| byte == b'\n')
+            .all(|line| line.len() <= 70));
+        assert_eq!(decode(&encoded).unwrap(), image);
+    }
+
+    #[test]
+    fn round_trip_binary_pgm() {
+        let image = gray_image();
+        assert_eq!(decode(&encode(&image, Kind::Gray, false)).unwrap(), image);
+    }
+
+    #[test]
+    fn round_trip_plain_pgm() {
+        let image = gray_image();
+        assert_eq!(decode(&encode(&image, Kind::Gray, true)).unwrap(), image);
+    }
+
+    #[test]
+    fn comments_and_max_value() {
+        let decoded = decode(b"P2 # a comment\n2 1\n# another one\n15\n0 15\n").unwrap();
+        assert_eq!(decoded.data, [0, 0, 0, 255, 255, 255, 255, 255]);
+    }
+}
@@ -0,0 +1,176 @@
+use super::{checked_data_size, invalid, RgbaImage};
+use std::error::Error;
+
+/// Decodes a QOI image. The color space byte is ignored.
+pub fn decode(data: &[u8]) -> Result<RgbaImage, Box<dyn Error>> {
+    if data.len() < HEADER_SIZE + END_MARKER.len() || &data[..4] != MAGIC {
+        return Err(invalid("not a QOI file"));
+    }
+    let width = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    let height = u32::from_be_bytes([data[8], data[9], data[10], data[11]]);
+    let size = checked_data_size(width, height)?;
+
+    let chunks = &data[HEADER_SIZE..data.len() - END_MARKER.len()];
+    let mut offset = 0;
+    let mut next = |count: usize| -> Result<&[u8], Box<dyn Error>> {
+        let bytes = chunks
+            .get(offset..offset + count)
+            .ok_or_else(|| invalid("truncated pixel data"))?;
+        offset += count;
+        Ok(bytes)
+    };
+
+    let mut result = Vec::with_capacity(size);
+    let mut seen = [[0u8; 4]; 64];
+    let mut pixel = [0, 0, 0, 255];
+    while result.len() < size {
+        let tag = next(1)?[0];
+        let mut run = 1;
+        match tag {
+            OP_RGB => {
+                pixel[..3].copy_from_slice(next(3)?);
+            }
+            OP_RGBA => {
+                pixel.copy_from_slice(next(4)?);
+            }
+            _ => match tag & MASK_2 {
+                OP_INDEX => pixel = seen[tag as usize],
+                OP_DIFF => {
+                    pixel[0] = pixel[0].wrapping_add((tag >> 4) & 3).wrapping_sub(2);
+                    pixel[1] = pixel[1].wrapping_add((tag >> 2) & 3).wrapping_sub(2);
+                    pixel[2] = pixel[2].wrapping_add(tag & 3).wrapping_sub(2);
+                }
+                OP_LUMA => {
+                    let byte = next(1)?[0];
+                    let green_diff = (tag & 0x3f).wrapping_sub(32);
+                    pixel[0] = pixel[0]
+                        .wrapping_add(green_diff)
+                        .wrapping_add(byte >> 4)
+                        .wrapping_sub(8);
+                    pixel[1] = pixel[1].wrapping_add(green_diff);
+                    pixel[2] = pixel[2]
+                        .wrapping_add(green_diff)
+                        .wrapping_add(byte & 0x0f)
+                        .wrapping_sub(8);
+                }
+                _ => run = (tag & 0x3f) as usize + 1,
+            },
+        }
+        seen[hash(pixel)] = pixel;
+        for _ in 0..run.min((size - result.len()) / 4) {
+            result.extend_from_slice(&pixel);
+        }
+    }
+
+    Ok(RgbaImage {
+        width,
+        height,
+        data: result,
+    })
+}
+
+/// Encodes an image as a QOI with the sRGB color space.
+pub fn encode(image: &RgbaImage) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_SIZE + image.data.len() + END_MARKER.len());
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&image.width.to_be_bytes());
+    buf.extend_from_slice(&image.height.to_be_bytes());
+    // Four channels, sRGB with linear alpha.
+    buf.extend_from_slice(&[4, 0]);
+
+    let mut seen = [[0u8; 4]; 64];
+    let mut previous = [0, 0, 0, 255];
+    let mut run = 0;
+    for chunk in image.data.chunks_exact(4) {
+        let pixel = [chunk[0], chunk[1], chunk[2], chunk[3]];
+        if pixel == previous {
+            run += 1;
+            if run == MAX_RUN {
+                buf.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            buf.push(OP_RUN | (run - 1));
+            run = 0;
+        }
+
+        let index = hash(pixel);
+        if seen[index] == pixel {
+            buf.push(OP_INDEX | index as u8);
+        } else if pixel[3] != previous[3] {
+            buf.push(OP_RGBA);
+            buf.extend_from_slice(&pixel);
+        } else {
+            let red_diff = pixel[0].wrapping_sub(previous[0]) as i8;
+            let green_diff = pixel[1].wrapping_sub(previous[1]) as i8;
+            let blue_diff = pixel[2].wrapping_sub(previous[2]) as i8;
+            let red_green = red_diff.wrapping_sub(green_diff);
+            let blue_green = blue_diff.wrapping_sub(green_diff);
+            if [red_diff, green_diff, blue_diff]
+                .iter()
+                .all(|diff| (-2..2).contains(diff))
+            {
+                buf.push(
+                    OP_DIFF
+                        | ((red_diff + 2) as u8) << 4
+                        | ((green_diff + 2) as u8) << 2
+                        | (blue_diff + 2) as u8,
+                );
+            } else if (-32..32).contains(&green_diff)
+                && (-8..8).contains(&red_green)
+                && (-8..8).contains(&blue_green)
+            {
+                buf.push(OP_LUMA | (green_diff + 32) as u8);
+                buf.push(((red_green + 8) as u8) << 4 | (blue_green + 8) as u8);
+            } else {
+                buf.push(OP_RGB);
+                buf.extend_from_slice(&pixel[..3]);
+            }
+        }
+        seen[index] = pixel;
+        previous = pixel;
+    }
+    if run > 0 {
+        buf.push(OP_RUN | (run - 1));
+    }
+    buf.extend_from_slice(&END_MARKER);
+    buf
+}
+
+fn hash([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+const MAGIC: &[u8] = b"qoif";
+const HEADER_SIZE: usize = 14;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+const MAX_RUN: u8 = 62;
+const MASK_2: u8 = 0xc0;
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xc0;
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_io::test_image;
+
+    #[test]
+    fn round_trip() {
+        let image = test_image(100, 7, false);
+        let encoded = encode(&image);
+        assert!(encoded.len() < image.data.len());
+        assert_eq!(decode(&encoded).unwrap(), image);
+    }
+
+    #[test]
+    fn round_trip_opaque() {
+        let image = test_image(13, 7, true);
+        assert_eq!(decode(&encode(&image)).unwrap(), image);
+    }
+}
@@ -0,0 +1,239 @@
+use super::{checked_data_size, invalid, unsupported, RgbaImage};
+use std::error::Error;
+
+/// Decodes true-color and grayscale TGA images, both raw and run-length encoded.
+pub fn decode(data: &[u8]) -> Result<RgbaImage, Box<dyn Error>> {
+    if data.len() < HEADER_SIZE {
+        return Err(invalid("not a TGA file"));
+    }
+    let id_length = data[0] as usize;
+    let color_map_type = data[1];
+    let image_type = data[2];
+    let color_map_length = u16::from_le_bytes([data[5], data[6]]) as usize;
+    let color_map_entry_bits = data[7] as usize;
+    let width = u16::from_le_bytes([data[12], data[13]]) as u32;
+    let height = u16::from_le_bytes([data[14], data[15]]) as u32;
+    let bits_per_pixel = data[16];
+    let descriptor = data[17];
+    let size = checked_data_size(width, height)?;
+
+    let (rle, gray) = match image_type {
+        TYPE_TRUE_COLOR => (false, false),
+        TYPE_GRAY => (false, true),
+        TYPE_RLE_TRUE_COLOR => (true, false),
+        TYPE_RLE_GRAY => (true, true),
+        _ => return Err(unsupported(&format!("TGA image type {}", image_type))),
+    };
+    let bytes_per_pixel = match (gray, bits_per_pixel) {
+        (false, 24) => 3,
+        (false, 32) => 4,
+        (true, 8) => 1,
+        (true, 16) => 2,
+        _ => {
+            return Err(unsupported(&format!(
+                "TGA with {} bits per pixel",
+                bits_per_pixel
+            )))
+        }
+    };
+
+    // Skip the image ID and the color map, which true-color images do not use.
+    let color_map_size = if color_map_type == 1 {
+        color_map_length * color_map_entry_bits.div_ceil(8)
+    } else {
+        0
+    };
+    let mut offset = HEADER_SIZE + id_length + color_map_size;
+    let pixel_count = width as usize * height as usize;
+    let mut pixels = Vec::with_capacity(size);
+    let mut push_pixel = |pixel: &[u8]| {
+        let rgba = match *pixel {
+            [l] => [l, l, l, 255],
+            [l, a] => [l, l, l, a],
+            [b, g, r] => [r, g, b, 255],
+            [b, g, r, a] => [r, g, b, a],
+            _ => unreachable!(),
+        };
+        pixels.extend_from_slice(&rgba);
+    };
+
+    let mut read = |count: usize| -> Result<&[u8], Box<dyn Error>> {
+        let bytes = data
+            .get(offset..offset + count)
+            .ok_or_else(|| invalid("truncated pixel data"))?;
+        offset += count;
+        Ok(bytes)
+    };
+    let mut decoded = 0;
+    while decoded < pixel_count {
+        if rle {
+            let packet = read(1)?[0];
+            let count = (packet & 0x7f) as usize + 1;
+            if decoded + count > pixel_count {
+                return Err(invalid("run-length packet crosses the end of the image"));
+            }
+            if packet & 0x80 != 0 {
+                let pixel = read(bytes_per_pixel)?;
+                for _ in 0..count {
+                    push_pixel(pixel);
+                }
+            } else {
+                for pixel in read(count * bytes_per_pixel)?.chunks_exact(bytes_per_pixel) {
+                    push_pixel(pixel);
+                }
+            }
+            decoded += count;
+        } else {
+            for pixel in read(pixel_count * bytes_per_pixel)?.chunks_exact(bytes_per_pixel) {
+                push_pixel(pixel);
+            }
+            decoded = pixel_count;
+        }
+    }
+
+    // Rows are stored from the bottom to the top, unless the descriptor says otherwise.
+    if descriptor & DESCRIPTOR_TOP_TO_BOTTOM == 0 {
+        pixels = pixels
+            .chunks_exact(width as usize * 4)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+    }
+    if descriptor & DESCRIPTOR_RIGHT_TO_LEFT != 0 {
+        for row in pixels.chunks_exact_mut(width as usize * 4) {
+            let mut reversed: Vec<u8> = row.chunks_exact(4).rev().flatten().copied().collect();
+            row.swap_with_slice(&mut reversed);
+        }
+    }
+
+    Ok(RgbaImage {
+        width,
+        height,
+        data: pixels,
+    })
+}
+
+/// Encodes an image as a top-to-bottom 32-bit TGA, optionally run-length encoded.
+pub fn encode(image: &RgbaImage, rle: bool) -> Result<Vec<u8>, Box<dyn Error>> {
+    if image.width > u16::MAX as u32 || image.height > u16::MAX as u32 {
+        return Err(unsupported(
+            "TGA images cannot be wider or higher than 65535 pixels",
+        ));
+    }
+    let mut buf = Vec::with_capacity(HEADER_SIZE + image.data.len());
+    buf.extend_from_slice(&[
+        0,
+        0,
+        if rle {
+            TYPE_RLE_TRUE_COLOR
+        } else {
+            TYPE_TRUE_COLOR
+        },
+    ]);
+    // No color map and zero origin.
+    buf.extend_from_slice(&[0; 9]);
+    buf.extend_from_slice(&(image.width as u16).to_le_bytes());
+    buf.extend_from_slice(&(image.height as u16).to_le_bytes());
+    // 32 bits per pixel, 8 of which are alpha.
+    buf.extend_from_slice(&[32, DESCRIPTOR_TOP_TO_BOTTOM | 8]);
+
+    let bgra: Vec<[u8; 4]> = image
+        .data
+        .chunks_exact(4)
+        .map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+        .collect();
+    if !rle {
+        buf.extend(bgra.iter().flatten());
+        return Ok(buf);
+    }
+
+    // Packets must not cross row boundaries.
+    for row in bgra.chunks(image.width as usize) {
+        let mut index = 0;
+        while index < row.len() {
+            let run = row[index..]
+                .iter()
+                .take(MAX_PACKET_LENGTH)
+                .take_while(|&&pixel| pixel == row[index])
+                .count();
+            if run > 1 {
+                buf.push(0x80 | (run - 1) as u8);
+                buf.extend_from_slice(&row[index]);
+                index += run;
+                continue;
+            }
+            // Collect raw pixels until the next run of at least two equal pixels.
+            let mut end = index + 1;
+            while end < row.len() && end - index < MAX_PACKET_LENGTH && row[end] != row[end - 1] {
+                end += 1;
+            }
+            if end < row.len() && row[end] == row[end - 1] && end - 1 > index {
+                end -= 1;
+            }
+            buf.push((end - index - 1) as u8);
+            buf.extend(row[index..end].iter().flatten());
+            index = end;
+        }
+    }
+    Ok(buf)
+}
+
+const HEADER_SIZE: usize = 18;
+const TYPE_TRUE_COLOR: u8 = 2;
+const TYPE_GRAY: u8 = 3;
+const TYPE_RLE_TRUE_COLOR: u8 = 10;
+const TYPE_RLE_GRAY: u8 = 11;
+const DESCRIPTOR_RIGHT_TO_LEFT: u8 = 0x10;
+const DESCRIPTOR_TOP_TO_BOTTOM: u8 = 0x20;
+const MAX_PACKET_LENGTH: usize = 128;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_io::test_image;
+
+    #[test]
+    fn round_trip_raw() {
+        let image = test_image(13, 7, false);
+        assert_eq!(decode(&encode(&image, false).unwrap()).unwrap(), image);
+    }
+
+    #[test]
+    fn round_trip_rle() {
+        let image = test_image(13, 7, false);
+        let encoded = encode(&image, true).unwrap();
+        assert!(encoded.len() < encode(&image, false).unwrap().len());
+        assert_eq!(decode(&encoded).unwrap(), image);
+    }
+
+    #[test]
+    fn round_trip_rle_long_runs() {
+        let image = RgbaImage {
+            width: 300,
+            height: 2,
+            data: [1, 2, 3, 4].repeat(600),
+        };
+        assert_eq!(decode(&encode(&image, true).unwrap()).unwrap(), image);
+    }
+
+    #[test]
+    fn bottom_to_top() {
+        let image = test_image(3, 2, true);
+        let mut encoded = encode(&image, false).unwrap();
+        encoded[17] &= !DESCRIPTOR_TOP_TO_BOTTOM;
+        let flipped = decode(&encoded).unwrap();
+        assert_eq!(flipped.data[..12], image.data[12..]);
+        assert_eq!(flipped.data[12..], image.data[..12]);
+    }
+
+    #[test]
+    fn reject_too_large() {
+        let image = RgbaImage {
+            width: 65536,
+            height: 1,
+            data: vec![0; 65536 * 4],
+        };
+        assert!(encode(&image, false).is_err());
+    }
+}
@@ -0,0 +1,572 @@
+use crate::{KeyModifier, KeyWithMod};
+use sdl2::keyboard::Keycode;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Where a binding is active.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Context {
+    Global,
+    /// Only while the tool with this name is selected. Such bindings take precedence over
+    /// the global ones.
+    Tool(String),
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Context::Global => write!(formatter, "global"),
+            Context::Tool(name) => write!(formatter, "tool {}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Binding {
+    pub context: Context,
+    /// The chords which have to be pressed one after another.
+    pub sequence: Vec<KeyWithMod>,
+    pub action: String,
+}
+
+impl Binding {
+    pub fn global(key: KeyWithMod, action: &str) -> Binding {
+        Binding {
+            context: Context::Global,
+            sequence: vec![key],
+            action: action.to_owned(),
+        }
+    }
+
+    /// Whether both bindings can be triggered by the same keys, so that one of them can never
+    /// be used.
+    pub fn conflicts_with(&self, other: &Binding) -> bool {
+        let len = self.sequence.len().min(other.sequence.len());
+        self.context == other.context && self.sequence[..len] == other.sequence[..len]
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(formatter, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Maps key sequences to action names and keeps track of partially typed sequences.
+pub struct Keymap {
+    actions: Vec<String>,
+    tools: Vec<String>,
+    bindings: Vec<Binding>,
+    /// Chords typed so far which are a prefix of some binding.
+    pending: Vec<KeyWithMod>,
+    /// Actions triggered by keys which are still held, to be released along with the key.
+    held: Vec<(Keycode, String)>,
+}
+
+impl Keymap {
+    /// Creates a keymap which knows about the given actions and tools and has the default
+    /// bindings.
+    pub fn new(actions: Vec<String>, tools: Vec<String>, defaults: Vec<Binding>) -> Keymap {
+        Keymap {
+            actions,
+            tools,
+            bindings: defaults,
+            pending: Vec::new(),
+            held: Vec::new(),
+        }
+    }
+
+    /// Reads the user's configuration file, if there is one, and applies it on top of the
+    /// current bindings. Problems in the file are returned rather than treated as fatal.
+    pub fn load_user_config(&mut self) -> Vec<String> {
+        let path = match config_path() {
+            Some(path) => path,
+            None => return Vec::new(),
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => self
+                .apply_config(&text)
+                .into_iter()
+                .map(|e| format!("{}: {}", path.display(), e))
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => vec![format!("{}: {}", path.display(), e)],
+        }
+    }
+
+    /// Applies bindings from a configuration file. The first line mentioning an action in a
+    /// context replaces its default bindings there, and further lines add more of them.
+    /// A binding which conflicts with a default one replaces it, and a binding which
+    /// conflicts with an earlier line of the file is ignored. Lines in a section which names
+    /// an unknown tool are ignored as well.
+    pub fn apply_config(&mut self, text: &str) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        let mut context = Some(Context::Global);
+        // Bindings from the file, with the lines they come from.
+        let mut configured: Vec<(Binding, usize)> = Vec::new();
+        let mut replaced: Vec<(Context, String)> = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let mut error = |message: String| {
+                errors.push(ConfigError {
+                    line: line_number,
+                    message,
+                })
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                context = match parse_context(&line[1..line.len() - 1]) {
+                    Some(Context::Tool(name)) if !self.tools.contains(&name) => {
+                        error(format!("Unknown tool: {}", name));
+                        None
+                    }
+                    Some(new_context) => Some(new_context),
+                    None => {
+                        error(format!("Unknown section: {}", line));
+                        None
+                    }
+                };
+                continue;
+            }
+            let context = match &context {
+                Some(context) => context,
+                None => continue,
+            };
+
+            let (action, keys) = match line.find('=') {
+                Some(position) => (line[..position].trim(), line[position + 1..].trim()),
+                None => {
+                    error(format!("Expected `action = keys`, found: {}", line));
+                    continue;
+                }
+            };
+            if !self.actions.iter().any(|known| known == action) {
+                error(format!("Unknown action: {}", action));
+                continue;
+            }
+            let sequence = match parse_sequence(keys) {
+                Ok(sequence) => sequence,
+                Err(e) => {
+                    error(e);
+                    continue;
+                }
+            };
+
+            let key = (context.clone(), action.to_owned());
+            if !replaced.contains(&key) {
+                self.bindings
+                    .retain(|binding| binding.context != key.0 || binding.action != key.1);
+                replaced.push(key);
+            }
+            // An empty list of keys just removes the bindings.
+            if sequence.is_empty() {
+                continue;
+            }
+            let binding = Binding {
+                context: context.clone(),
+                sequence,
+                action: action.to_owned(),
+            };
+            if let Some((other, other_line)) = configured
+                .iter()
+                .find(|(other, _)| other.conflicts_with(&binding))
+            {
+                error(format!(
+                    "{} conflicts with {} bound on line {}, ignoring it",
+                    format_sequence(&binding.sequence),
+                    other.action,
+                    other_line
+                ));
+                continue;
+            }
+            for other in self
+                .bindings
+                .iter()
+                .filter(|other| other.conflicts_with(&binding))
+            {
+                error(format!(
+                    "{} replaces the default binding {} of {}",
+                    format_sequence(&binding.sequence),
+                    format_sequence(&other.sequence),
+                    other.action
+                ));
+            }
+            self.bindings
+                .retain(|other| !other.conflicts_with(&binding));
+            self.bindings.push(binding.clone());
+            configured.push((binding, line_number));
+        }
+        errors
+    }
+
+    /// The sequences bound to an action while the given tool is selected, the ones specific to
+    /// the tool first.
+    pub fn sequences_of(&self, action: &str, tool_name: &str) -> Vec<&[KeyWithMod]> {
+        let tool_context = Context::Tool(tool_name.to_owned());
+        let mut sequences = Vec::new();
+        for context in &[tool_context, Context::Global] {
+            sequences.extend(
+                self.bindings
+                    .iter()
+                    .filter(|b| &b.context == context && b.action == action)
+                    .map(|b| b.sequence.as_slice()),
+            );
+        }
+        sequences
+    }
+
+    /// Pairs of bindings which conflict with each other.
+    pub fn conflicts(&self) -> Vec<(&Binding, &Binding)> {
+        let mut conflicts = Vec::new();
+        for (index, first) in self.bindings.iter().enumerate() {
+            for second in &self.bindings[index + 1..] {
+                if first.conflicts_with(second) {
+                    conflicts.push((first, second));
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Handles a key press while the given tool is selected. Returns the action to run once
+    /// a whole sequence has been typed.
+    pub fn press(&mut self, key: KeyWithMod, tool_name: &str) -> Option<String> {
+        // Pressing a modifier on its way to the next chord must not break off a sequence.
+        if is_modifier(key.key) {
+            return None;
+        }
+        self.pending.push(key);
+        let mut result = self.lookup(tool_name);
+        if result == Lookup::Nothing && self.pending.len() > 1 {
+            // The sequence has been broken off, but the last key may start a new one.
+            self.pending.clear();
+            self.pending.push(key);
+            result = self.lookup(tool_name);
+        }
+        match result {
+            Lookup::Action(action) => {
+                self.pending.clear();
+                let held = (key.key, action.clone());
+                if !self.held.contains(&held) {
+                    self.held.push(held);
+                }
+                Some(action)
+            }
+            Lookup::Prefix => None,
+            Lookup::Nothing => {
+                self.pending.clear();
+                None
+            }
+        }
+    }
+
+    /// Handles a key release, returning the actions which have been triggered by this key.
+    pub fn release(&mut self, key: Keycode) -> Vec<String> {
+        let (released, held) = self.held.drain(..).partition(|(held, _)| *held == key);
+        self.held = held;
+        released.into_iter().map(|(_, action)| action).collect()
+    }
+
+    fn lookup(&self, tool_name: &str) -> Lookup {
+        let tool_context = Context::Tool(tool_name.to_owned());
+        for context in &[tool_context, Context::Global] {
+            let mut is_prefix = false;
+            for binding in self.bindings.iter().filter(|b| &b.context == context) {
+                if binding.sequence == self.pending {
+                    return Lookup::Action(binding.action.clone());
+                }
+                is_prefix |= binding.sequence.starts_with(&self.pending);
+            }
+            if is_prefix {
+                return Lookup::Prefix;
+            }
+        }
+        Lookup::Nothing
+    }
+
+    /// Renders the bindings in the configuration file format.
+    pub fn to_config(&self) -> String {
+        let mut config = String::from(CONFIG_HEADER);
+        let mut contexts: Vec<&Context> = Vec::new();
+        for binding in &self.bindings {
+            if !contexts.contains(&&binding.context) {
+                contexts.push(&binding.context);
+            }
+        }
+        for context in contexts {
+            config.push_str(&format!("\n[{}]\n", context));
+            for binding in self.bindings.iter().filter(|b| &b.context == context) {
+                config.push_str(&format!(
+                    "{} = {}\n",
+                    binding.action,
+                    format_sequence(&binding.sequence)
+                ));
+            }
+        }
+        config
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Lookup {
+    Action(String),
+    /// The keys typed so far start some sequence.
+    Prefix,
+    Nothing,
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    Some(
+        dirs_next::config_dir()?
+            .join("oxipaint")
+            .join(CONFIG_FILE_NAME),
+    )
+}
+
+fn parse_context(text: &str) -> Option<Context> {
+    let text = text.trim();
+    if text == "global" {
+        return Some(Context::Global);
+    }
+    let name = text.strip_prefix("tool ")?.trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some(Context::Tool(name.to_owned()))
+}
+
+/// Parses chords separated by spaces, such as `Ctrl+K Ctrl+C`.
+pub fn parse_sequence(text: &str) -> Result<Vec<KeyWithMod>, String> {
+    text.split_whitespace().map(parse_chord).collect()
+}
+
+/// Parses a chord such as `Ctrl+Shift+Z`. Spaces in key names are written as underscores,
+/// e.g. `Keypad_Enter`.
+pub fn parse_chord(text: &str) -> Result<KeyWithMod, String> {
+    let mut modifier = KeyModifier::new();
+    let mut rest = text;
+    // Modifiers are matched as prefixes, so that `Ctrl++` is Ctrl and the plus key.
+    loop {
+        let lowercase = rest.to_ascii_lowercase();
+        if lowercase.starts_with("ctrl+") {
+            modifier = modifier.ctrl();
+        } else if lowercase.starts_with("shift+") {
+            modifier = modifier.shift();
+        } else if lowercase.starts_with("alt+") {
+            modifier = modifier.alt();
+        } else {
+            break;
+        }
+        rest = &rest[lowercase.find('+').unwrap() + 1..];
+    }
+    let name = if rest == "_" {
+        rest.to_owned()
+    } else {
+        rest.replace('_', " ")
+    };
+    match Keycode::from_name(&name) {
+        Some(key) if is_modifier(key) => Err(format!(
+            "Modifier keys cannot be bound on their own: {}",
+            text
+        )),
+        Some(key) => Ok(modifier.key(key)),
+        None => Err(format!("Unknown key in {}", text)),
+    }
+}
+
+/// Whether the key only modifies other keys, so that it never makes up a chord by itself.
+fn is_modifier(key: Keycode) -> bool {
+    matches!(
+        key,
+        Keycode::LCtrl
+            | Keycode::RCtrl
+            | Keycode::LShift
+            | Keycode::RShift
+            | Keycode::LAlt
+            | Keycode::RAlt
+            | Keycode::LGui
+            | Keycode::RGui
+            | Keycode::Mode
+    )
+}
+
+pub fn format_sequence(sequence: &[KeyWithMod]) -> String {
+    let chords: Vec<String> = sequence.iter().map(|&chord| format_chord(chord)).collect();
+    chords.join(" ")
+}
+
+pub fn format_chord(chord: KeyWithMod) -> String {
+    let mut text = String::new();
+    if chord.modifier.ctrl {
+        text.push_str("Ctrl+");
+    }
+    if chord.modifier.alt {
+        text.push_str("Alt+");
+    }
+    if chord.modifier.shift {
+        text.push_str("Shift+");
+    }
+    let name = chord.key.name();
+    if name == "_" {
+        text.push_str(&name);
+    } else {
+        text.push_str(&name.replace(' ', "_"));
+    }
+    text
+}
+
+const CONFIG_FILE_NAME: &str = "keybindings.conf";
+
+const CONFIG_HEADER: &str = "\
+# OxiPaint keybindings.
+#
+# Each line binds an action to a sequence of chords separated by spaces, for example
+# `add-checkpoint = Ctrl+K Ctrl+C`. Spaces in key names are written as underscores.
+# Repeat a line to bind an action to several sequences, or leave the keys empty to unbind
+# it. Bindings under a [tool NAME] section only work while that tool is selected.
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(text: &str) -> KeyWithMod {
+        parse_chord(text).unwrap()
+    }
+
+    fn keymap() -> Keymap {
+        let actions = ["undo", "redo", "save", "add-checkpoint"];
+        Keymap::new(
+            actions.iter().map(|&action| action.to_owned()).collect(),
+            vec!["Pencil".to_owned()],
+            vec![
+                Binding::global(chord("Ctrl+Z"), "undo"),
+                Binding::global(chord("Ctrl+Shift+Z"), "redo"),
+                Binding::global(chord("Ctrl+S"), "save"),
+            ],
+        )
+    }
+
+    #[test]
+    fn parse_chords() {
+        let ctrl_alt_a = KeyModifier::new().ctrl().alt().key(Keycode::A);
+        assert_eq!(parse_chord("Ctrl+Alt+A"), Ok(ctrl_alt_a));
+        assert_eq!(parse_chord("alt+ctrl+a"), Ok(ctrl_alt_a));
+        assert_eq!(
+            parse_chord("Ctrl++"),
+            Ok(KeyModifier::new().ctrl().key(Keycode::Plus))
+        );
+        assert_eq!(
+            parse_chord("Keypad_Enter"),
+            Ok(KeyModifier::new().key(Keycode::KpEnter))
+        );
+        assert!(parse_chord("Ctrl+Nonsense").is_err());
+        assert!(parse_chord("Ctrl+Left_Shift").is_err());
+        assert_eq!(
+            format_chord(chord("Shift+Alt+Ctrl+PageUp")),
+            "Ctrl+Alt+Shift+PageUp"
+        );
+        assert_eq!(
+            parse_sequence("Ctrl+K  Ctrl+C"),
+            Ok(vec![chord("Ctrl+K"), chord("Ctrl+C")])
+        );
+    }
+
+    #[test]
+    fn detect_conflicts() {
+        let mut keymap = keymap();
+        assert!(keymap.conflicts().is_empty());
+        keymap.bindings.push(Binding {
+            context: Context::Global,
+            sequence: vec![chord("Ctrl+S"), chord("A")],
+            action: "add-checkpoint".to_owned(),
+        });
+        keymap.bindings.push(Binding {
+            context: Context::Tool("Pencil".to_owned()),
+            sequence: vec![chord("Ctrl+Z")],
+            action: "redo".to_owned(),
+        });
+        let conflicts = keymap.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].0.action, "save");
+        assert_eq!(conflicts[0].1.action, "add-checkpoint");
+    }
+
+    #[test]
+    fn config_replaces_defaults() {
+        let mut keymap = keymap();
+        let errors = keymap.apply_config(
+            "# comment\n\
+             undo = Ctrl+K Ctrl+Z\n\
+             redo = Ctrl+K Ctrl+Z Ctrl+Z\n\
+             save = Ctrl+Z\n\
+             unknown = A\n\
+             [tool Pencil]\n\
+             redo = Ctrl+Z\n\
+             [tool Pencli]\n\
+             save = B\n",
+        );
+        let lines: Vec<usize> = errors.iter().map(|error| error.line).collect();
+        assert_eq!(lines, vec![3, 5, 8]);
+        assert_eq!(
+            keymap.sequences_of("undo", "Fill"),
+            vec![&[chord("Ctrl+K"), chord("Ctrl+Z")][..]]
+        );
+        assert_eq!(
+            keymap.sequences_of("redo", "Pencil"),
+            vec![&[chord("Ctrl+Z")][..]]
+        );
+        assert_eq!(
+            keymap.sequences_of("save", "Fill"),
+            vec![&[chord("Ctrl+Z")][..]]
+        );
+        assert_eq!(
+            keymap.sequences_of("save", "Pencil"),
+            vec![&[chord("Ctrl+Z")][..]]
+        );
+    }
+
+    #[test]
+    fn match_sequences() {
+        let mut keymap = keymap();
+        keymap.apply_config("add-checkpoint = Ctrl+K Shift+C\n[tool Pencil]\nsave = Ctrl+Z\n");
+        assert_eq!(keymap.press(chord("Ctrl+K"), "Fill"), None);
+        // Modifiers pressed and released between the chords do not break the sequence.
+        let left_shift = KeyModifier::new().shift().key(Keycode::LShift);
+        assert_eq!(keymap.press(left_shift, "Fill"), None);
+        assert_eq!(
+            keymap.press(chord("Shift+C"), "Fill"),
+            Some("add-checkpoint".to_owned())
+        );
+
+        // A broken off sequence starts over with the last key.
+        assert_eq!(keymap.press(chord("Ctrl+K"), "Fill"), None);
+        assert_eq!(
+            keymap.press(chord("Ctrl+Z"), "Fill"),
+            Some("undo".to_owned())
+        );
+
+        // Tool bindings take precedence over the global ones.
+        assert_eq!(
+            keymap.press(chord("Ctrl+Z"), "Pencil"),
+            Some("save".to_owned())
+        );
+        assert_eq!(keymap.release(Keycode::Z), vec!["undo", "save"]);
+        assert!(keymap.release(Keycode::Z).is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+/// A stable identifier of a layer, which does not change when layers are reordered.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct LayerId(pub u32);
+
+impl fmt::Display for LayerId {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "#{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub id: LayerId,
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f64,
+    pub locked: bool,
+    /// Pixel data in the same BGRA layout as the canvas texture.
+    pub data: Vec<u8>,
+}
+
+impl Layer {
+    pub fn new(id: LayerId, name: String, data: Vec<u8>) -> Layer {
+        Layer {
+            id,
+            name,
+            visible: true,
+            opacity: 1.0,
+            locked: false,
+            data,
+        }
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.opacity >= 1.0
+    }
+}
+
+/// Blends a row of BGRA pixels of a layer over a row of the composite image in place. The
+/// pixels usually come from `layer.data`, but may also be a copy with some of them replaced.
+pub fn composite_row(dst: &mut [u8], src: &[u8], layer: &Layer) {
+    for (dst, src) in dst.chunks_mut(4).zip(src.chunks(4)) {
+        if layer.is_opaque() && src[3] == 255 {
+            dst.copy_from_slice(src);
+        } else {
+            blend_over(dst, src, layer.opacity);
+        }
+    }
+}
+
+/// Blends a BGRA pixel over another one in place, using the "normal" blending mode.
+pub fn blend_over(dst: &mut [u8], src: &[u8], opacity: f64) {
+    let src_alpha = src[3] as f64 / 255.0 * opacity;
+    if src_alpha <= 0.0 {
+        return;
+    }
+    let dst_alpha = dst[3] as f64 / 255.0;
+    let out_alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+    for channel in 0..3 {
+        let s = src[channel] as f64 * src_alpha;
+        let d = dst[channel] as f64 * dst_alpha * (1.0 - src_alpha);
+        dst[channel] = ((s + d) / out_alpha).round().min(255.0) as u8;
+    }
+    dst[3] = (out_alpha * 255.0).round().min(255.0) as u8;
+}
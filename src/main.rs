@@ -1,40 +1,70 @@
 #![forbid(unsafe_code)]
 
+mod action;
+mod batch;
 mod canvas;
+mod cli;
+mod command_palette;
+mod dirty;
 mod draw_context;
 mod draw_primitives;
 mod editor;
 mod geometry;
+mod grid;
 mod history;
+mod history_panel;
+mod image_io;
+mod keymap;
+mod layer;
 mod overlay;
+mod patch;
+mod project;
+mod selection;
+mod snapshot;
+mod status_bar;
+#[cfg(test)]
+mod test_support;
+mod text;
+mod tile_grid;
 mod tool;
+mod toolbar;
 mod tools;
 mod zoom_overlay;
 
 #[macro_use]
 extern crate lazy_static;
 
-use crate::draw_context::DrawContext;
-use crate::editor::{Editor, TimeMachineError};
-use crate::geometry::Point;
-use crate::overlay::{EventResponse, Overlay};
+use crate::action::{ACTIONS, HOTKEYS};
+use crate::cli::Command;
+use crate::command_palette::{CommandPalette, PaletteEntry};
+use crate::draw_context::{Connectivity, DrawContext};
+use crate::editor::{Editor, LayerError, TimeMachineError, ZoomFit};
+use crate::geometry::{Point, Scale};
+use crate::history::NodeId;
+use crate::history_panel::HistoryPanel;
+use crate::keymap::{Binding, Keymap};
+use crate::layer::Layer;
+use crate::overlay::Overlay;
+use crate::selection::PixelBuffer;
+use crate::status_bar::{Status, StatusBar};
+use crate::text::TextRenderer;
 use crate::tool::Tool;
+use crate::toolbar::Toolbar;
 use crate::zoom_overlay::ZoomOverlay;
 use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::{Keycode, Mod};
 use sdl2::mouse::MouseButton;
 use sdl2::pixels::Color;
-use sdl2::ttf::Sdl2TtfContext;
 use sdl2::video::Window;
 use sdl2::{EventPump, Sdl};
 use std::cell::RefCell;
 use std::error::Error;
 use std::fmt;
-use std::fs::File;
-use std::io::BufWriter;
 use std::iter::FromIterator;
 use std::path::Path;
+use std::process;
 use std::rc::Rc;
+use tinyfiledialogs::{MessageBoxIcon, YesNo};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct SdlError(String);
@@ -59,7 +89,7 @@ pub struct SdlApp {
     pub sdl_context: Sdl,
     pub sdl_canvas: Rc<RefCell<SdlCanvas>>,
     pub event_pump: EventPump,
-    pub ttf_context: Sdl2TtfContext,
+    pub text: TextRenderer,
 }
 
 impl SdlApp {
@@ -84,15 +114,22 @@ impl SdlApp {
         let event_pump = sdl_context.event_pump()?;
 
         let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
+        let text = TextRenderer::new(ttf_context, sdl_canvas.borrow().texture_creator())?;
 
         Ok(SdlApp {
             sdl_context,
             sdl_canvas,
             event_pump,
-            ttf_context,
+            text,
         })
     }
 
+    /// The maximum size of a texture, or zero in a dimension which is not limited.
+    pub fn max_texture_size(&self) -> (u32, u32) {
+        let info = self.sdl_canvas.borrow().info();
+        (info.max_texture_width, info.max_texture_height)
+    }
+
     pub fn cursor_position(&self) -> Point<i32> {
         let mouse_state = self.event_pump.mouse_state();
         Point::new(mouse_state.x(), mouse_state.y())
@@ -188,8 +225,8 @@ impl From<Mod> for KeyModifier {
     fn from(sdl_keymod: Mod) -> Self {
         let mut result = KeyModifier::new();
         gen_keymod_translation!(sdl_keymod, [Mod::LCTRLMOD, Mod::RCTRLMOD] => result.ctrl);
-        gen_keymod_translation!(sdl_keymod, [Mod::LSHIFTMOD, Mod::RSHIFTMOD] => result.alt);
-        gen_keymod_translation!(sdl_keymod, [Mod::LALTMOD, Mod::RALTMOD] => result.shift);
+        gen_keymod_translation!(sdl_keymod, [Mod::LSHIFTMOD, Mod::RSHIFTMOD] => result.shift);
+        gen_keymod_translation!(sdl_keymod, [Mod::LALTMOD, Mod::RALTMOD] => result.alt);
         result
     }
 }
@@ -200,7 +237,10 @@ mod hotkey {
     pub fn handle_undo(oxipaint: &mut OxiPaint) {
         match oxipaint.editor.undo() {
             Ok(_) => {
-                println!("Undo OK");
+                println!(
+                    "Undo OK, history takes {} KiB",
+                    oxipaint.editor.history_memory_usage() / 1024
+                );
                 oxipaint.enqueue_redraw();
             }
             Err(TimeMachineError::AlreadyAtTimeEdge) => {
@@ -215,7 +255,10 @@ mod hotkey {
     pub fn handle_redo(oxipaint: &mut OxiPaint) {
         match oxipaint.editor.redo() {
             Ok(_) => {
-                println!("Redo OK");
+                println!(
+                    "Redo OK, history takes {} KiB",
+                    oxipaint.editor.history_memory_usage() / 1024
+                );
                 oxipaint.enqueue_redraw();
             }
             Err(TimeMachineError::AlreadyAtTimeEdge) => {
@@ -227,24 +270,463 @@ mod hotkey {
         }
     }
 
+    pub fn jump_in_history(oxipaint: &mut OxiPaint, target: NodeId) {
+        report_history_jump(oxipaint, |editor| editor.jump_to(target));
+    }
+
+    pub fn switch_branch(oxipaint: &mut OxiPaint, delta: isize) {
+        report_history_jump(oxipaint, |editor| editor.switch_branch(delta));
+    }
+
+    pub fn step_chronologically(oxipaint: &mut OxiPaint, delta: isize) {
+        report_history_jump(oxipaint, |editor| editor.step_chronologically(delta));
+    }
+
+    fn report_history_jump(
+        oxipaint: &mut OxiPaint,
+        jump: impl FnOnce(&mut Editor) -> Result<(), TimeMachineError>,
+    ) {
+        match jump(&mut oxipaint.editor) {
+            Ok(_) => {
+                let history = oxipaint.editor.history();
+                let current = history.current();
+                let (branch, branch_count) = history.branch_position(current);
+                println!(
+                    "Now at: {} (branch {}/{})",
+                    history.label(current),
+                    branch + 1,
+                    branch_count
+                );
+                oxipaint.enqueue_redraw();
+            }
+            Err(TimeMachineError::AlreadyAtTimeEdge) => {
+                println!("There is nothing to jump to in the history");
+            }
+            Err(TimeMachineError::TransactionInProgress) => {
+                println!("Cannot jump in history because a drawing action is in progress");
+            }
+        }
+    }
+
+    pub fn toggle_history_panel(oxipaint: &mut OxiPaint) {
+        oxipaint.history_panel.toggle();
+        oxipaint.enqueue_redraw();
+    }
+
+    pub fn add_checkpoint(oxipaint: &mut OxiPaint) {
+        let default_name = format!(
+            "Checkpoint {}",
+            oxipaint.editor.history().checkpoints().len() + 1
+        );
+        let name = match tinyfiledialogs::input_box("Checkpoint", "Checkpoint name:", &default_name)
+        {
+            Some(name) => name,
+            None => return,
+        };
+        match oxipaint.editor.add_checkpoint(name.clone()) {
+            Ok(_) => {
+                println!("Added checkpoint: {}", name);
+                oxipaint.enqueue_redraw();
+            }
+            Err(_) => println!("Cannot add a checkpoint because a drawing action is in progress"),
+        }
+    }
+
+    pub fn revert_to_checkpoint(oxipaint: &mut OxiPaint) {
+        let history = oxipaint.editor.history();
+        let last = match history.checkpoints().last() {
+            Some(checkpoint) => checkpoint.name.clone(),
+            None => {
+                println!("There are no checkpoints to revert to");
+                return;
+            }
+        };
+        let names: Vec<&str> = history
+            .checkpoints()
+            .iter()
+            .map(|checkpoint| checkpoint.name.as_str())
+            .collect();
+        let message = format!("Checkpoint to revert to:\n\n{}", names.join("\n"));
+        let name = match tinyfiledialogs::input_box("Revert to checkpoint", &message, &last) {
+            Some(name) => name,
+            None => return,
+        };
+        let checkpoint = match history.checkpoint_by_name(name.trim()) {
+            Some(checkpoint) => checkpoint.clone(),
+            None => {
+                println!("There is no checkpoint named {}", name.trim());
+                return;
+            }
+        };
+        println!("Reverting to checkpoint: {}", checkpoint.name);
+        jump_in_history(oxipaint, checkpoint.node);
+    }
+
+    fn report_layer_result(oxipaint: &mut OxiPaint, result: Result<(), LayerError>) {
+        match result {
+            Ok(_) => {
+                let canvas = oxipaint.editor.canvas();
+                let layer = canvas.active_layer();
+                println!(
+                    "Active layer: {} ({}/{}), visible: {}, locked: {}, opacity: {:.0}%",
+                    layer.name,
+                    canvas.active_layer_index() + 1,
+                    canvas.layers().len(),
+                    layer.visible,
+                    layer.locked,
+                    layer.opacity * 100.0,
+                );
+                oxipaint.enqueue_redraw();
+            }
+            Err(LayerError::TransactionInProgress) => {
+                println!("Cannot change layers because a drawing action is in progress");
+            }
+            Err(LayerError::LastLayer) => {
+                println!("Cannot remove the last layer");
+            }
+            Err(LayerError::NoSuchLayer) => {
+                println!("There is no layer in that direction");
+            }
+        }
+    }
+
+    pub fn add_layer(oxipaint: &mut OxiPaint) {
+        let result = oxipaint.editor.add_layer();
+        report_layer_result(oxipaint, result);
+    }
+
+    pub fn remove_layer(oxipaint: &mut OxiPaint) {
+        let result = oxipaint.editor.remove_active_layer();
+        report_layer_result(oxipaint, result);
+    }
+
+    pub fn select_layer(oxipaint: &mut OxiPaint, delta: isize) {
+        let result = oxipaint.editor.select_layer_relative(delta);
+        report_layer_result(oxipaint, result);
+    }
+
+    pub fn move_layer(oxipaint: &mut OxiPaint, delta: isize) {
+        let result = oxipaint.editor.move_active_layer(delta);
+        report_layer_result(oxipaint, result);
+    }
+
+    pub fn update_active_layer(oxipaint: &mut OxiPaint, func: impl Fn(&mut Layer)) {
+        oxipaint.editor.update_active_layer(func);
+        report_layer_result(oxipaint, Ok(()));
+    }
+
+    pub fn change_brush_size(oxipaint: &mut OxiPaint, delta: i32) {
+        let size = &mut oxipaint.draw_context.brush_size;
+        *size = (*size as i32 + delta).clamp(1, MAX_BRUSH_SIZE) as u32;
+        println!("Brush size: {}", size);
+    }
+
+    pub fn change_eraser_size(oxipaint: &mut OxiPaint, delta: i32) {
+        let size = &mut oxipaint.draw_context.eraser_size;
+        *size = (*size as i32 + delta).clamp(1, MAX_BRUSH_SIZE) as u32;
+        println!("Eraser size: {}", size);
+    }
+
+    const MAX_BRUSH_SIZE: i32 = 256;
+
+    pub fn swap_colors(oxipaint: &mut OxiPaint) {
+        let context = &mut oxipaint.draw_context;
+        std::mem::swap(&mut context.primary_color, &mut context.secondary_color);
+        println!(
+            "Primary color: {:?}, secondary color: {:?}",
+            context.primary_color, context.secondary_color
+        );
+    }
+
+    pub fn cycle_picker_size(oxipaint: &mut OxiPaint) {
+        let size = &mut oxipaint.draw_context.picker_size;
+        *size = if *size >= MAX_PICKER_SIZE {
+            1
+        } else {
+            *size + 2
+        };
+        println!("Color picker averages {0}x{0} pixels", size);
+    }
+
+    const MAX_PICKER_SIZE: u32 = 9;
+
+    pub fn toggle_shape_filled(oxipaint: &mut OxiPaint) {
+        let filled = &mut oxipaint.draw_context.shape_filled;
+        *filled = !*filled;
+        println!("Filled shapes: {}", filled);
+    }
+
+    pub fn change_fill_tolerance(oxipaint: &mut OxiPaint, delta: i32) {
+        let tolerance = &mut oxipaint.draw_context.fill_tolerance;
+        *tolerance = (*tolerance as i32 + delta).clamp(0, 255) as u8;
+        println!("Fill tolerance: {}", tolerance);
+    }
+
+    pub fn toggle_fill_connectivity(oxipaint: &mut OxiPaint) {
+        let connectivity = &mut oxipaint.draw_context.fill_connectivity;
+        *connectivity = match connectivity {
+            Connectivity::Four => Connectivity::Eight,
+            Connectivity::Eight => Connectivity::Four,
+        };
+        println!("Fill connectivity: {:?}", connectivity);
+    }
+
+    pub fn toggle_fill_global(oxipaint: &mut OxiPaint) {
+        let global = &mut oxipaint.draw_context.fill_global;
+        *global = !*global;
+        println!("Global fill: {}", global);
+    }
+
+    pub fn copy(oxipaint: &mut OxiPaint) {
+        match oxipaint.editor.copy_selection() {
+            Some(buffer) => {
+                println!("Copied {}x{} pixels", buffer.width(), buffer.height());
+                oxipaint.clipboard = Some(buffer);
+            }
+            None => println!("Nothing to copy"),
+        }
+    }
+
+    pub fn cut(oxipaint: &mut OxiPaint) {
+        match oxipaint.editor.cut_selection() {
+            Ok(Some(buffer)) => {
+                println!("Cut {}x{} pixels", buffer.width(), buffer.height());
+                oxipaint.clipboard = Some(buffer);
+                oxipaint.enqueue_redraw();
+            }
+            Ok(None) => println!("Nothing to cut"),
+            Err(_) => println!("Cannot cut because a drawing action is in progress"),
+        }
+    }
+
+    pub fn paste(oxipaint: &mut OxiPaint) {
+        let buffer = match &oxipaint.clipboard {
+            Some(buffer) => buffer.clone(),
+            None => {
+                println!("Nothing to paste");
+                return;
+            }
+        };
+        match oxipaint.editor.paste(buffer) {
+            Ok(_) => oxipaint.enqueue_redraw(),
+            Err(_) => println!("Cannot paste because a drawing action is in progress"),
+        }
+    }
+
+    pub fn deselect(oxipaint: &mut OxiPaint) {
+        oxipaint.editor.select(None);
+        oxipaint.enqueue_redraw();
+    }
+
+    pub fn zoom_to_fit(oxipaint: &mut OxiPaint, fit: ZoomFit) {
+        let (width, height) = oxipaint.get_screen_size();
+        let scale = oxipaint.editor.zoom_to_fit(width, height, fit);
+        oxipaint.show_zoom(scale);
+    }
+
+    pub fn zoom_to(oxipaint: &mut OxiPaint, scale: Scale) {
+        oxipaint.editor.set_scale(scale);
+        oxipaint.show_zoom(scale);
+    }
+
+    /// Steps the zoom level up or down, keeping the center of the view in place.
+    pub fn zoom_step(oxipaint: &mut OxiPaint, zoom_in: bool) {
+        let center = oxipaint.editor.center();
+        let scale = if zoom_in {
+            oxipaint.editor.scale_up(center)
+        } else {
+            oxipaint.editor.scale_down(center)
+        };
+        match scale {
+            Some(scale) => oxipaint.show_zoom(scale),
+            None => println!("Cannot zoom any further"),
+        }
+    }
+
+    pub fn ask_zoom(oxipaint: &mut OxiPaint) {
+        let current = oxipaint.editor.scale().to_percentage_string();
+        let input =
+            tinyfiledialogs::input_box("Zoom", "Zoom percentage:", current.trim_end_matches('%'));
+        if let Some(input) = input {
+            match input.trim().trim_end_matches('%').trim().parse::<f64>() {
+                Ok(percentage) => match Scale::from_percentage(percentage) {
+                    Some(scale) => {
+                        if (scale.factor() * 100.0 - percentage).abs() > 1e-9 {
+                            println!(
+                                "Zoom {}% is not supported, using {} instead",
+                                percentage,
+                                scale.to_percentage_string()
+                            );
+                        }
+                        zoom_to(oxipaint, scale);
+                    }
+                    None => println!(
+                        "Invalid zoom percentage: {} (must be above 0% and at most {})",
+                        input,
+                        Scale::MAX.to_percentage_string()
+                    ),
+                },
+                Err(_) => println!("Invalid zoom percentage: {}", input),
+            }
+        }
+    }
+
+    pub fn toggle_pixel_grid(oxipaint: &mut OxiPaint) {
+        let grid = oxipaint.editor.grid_mut();
+        grid.pixel_grid = !grid.pixel_grid;
+        println!("Pixel grid: {}", grid.pixel_grid);
+        oxipaint.enqueue_redraw();
+    }
+
+    pub fn toggle_tile_grid(oxipaint: &mut OxiPaint) {
+        let grid = oxipaint.editor.grid_mut();
+        grid.tile_grid = !grid.tile_grid;
+        println!("Tile grid: {}", grid.tile_grid);
+        oxipaint.enqueue_redraw();
+    }
+
+    pub fn cycle_tile_size(oxipaint: &mut OxiPaint) {
+        let grid = oxipaint.editor.grid_mut();
+        grid.next_tile_size();
+        println!("Tile grid: {}x{}", grid.tile_width, grid.tile_height);
+        oxipaint.enqueue_redraw();
+    }
+
     pub fn save(oxipaint: &mut OxiPaint) -> Result<(), Box<dyn Error>> {
         if let Some(path) = tinyfiledialogs::save_file_dialog("Save file", "image.png") {
-            use png::{ColorType, Encoder};
-            let file = File::create(Path::new(&path))?;
-            let mut file_writer = BufWriter::new(file);
-            let canvas = &oxipaint.editor.canvas();
-            let mut png_writer = Encoder::new(&mut file_writer, canvas.width(), canvas.height());
-            png_writer.set_color(ColorType::RGBA);
-            png_writer
-                .write_header()?
-                .write_image_data(&canvas.build_image())?;
-            println!("Saved to {}", path);
+            let path = Path::new(&path);
+            if project::is_project_path(path) {
+                if !save_project(oxipaint, path)? {
+                    return Ok(());
+                }
+            } else {
+                let format = image_io::Format::for_saving(path)?;
+                let question = match format {
+                    image_io::Format::Tga => "Save without compression?",
+                    _ => "Save as plain text?",
+                };
+                let plain = format.has_plain_variant()
+                    && tinyfiledialogs::message_box_yes_no(
+                        "Save image",
+                        question,
+                        MessageBoxIcon::Question,
+                        YesNo::No,
+                    ) == YesNo::Yes;
+                let canvas = oxipaint.editor.canvas_mut();
+                let image = image_io::RgbaImage {
+                    width: canvas.width(),
+                    height: canvas.height(),
+                    data: canvas.build_image(),
+                };
+                image_io::save(path, &image, format, plain)?;
+            }
+            oxipaint.editor.mark_saved();
+            println!("Saved to {}", path.display());
         } else {
             println!("Saving cancelled");
         }
         Ok(())
     }
 
+    /// Saves the project, asking whether to include the history. Returns `false` if saving
+    /// is not possible at the moment.
+    fn save_project(oxipaint: &mut OxiPaint, path: &Path) -> Result<bool, Box<dyn Error>> {
+        oxipaint.editor.commit_selection();
+        if oxipaint.editor.is_in_transaction() {
+            println!("Cannot save a project because a drawing action is in progress");
+            return Ok(false);
+        }
+        let include_history = tinyfiledialogs::message_box_yes_no(
+            "Save project",
+            "Include the undo history?",
+            MessageBoxIcon::Question,
+            YesNo::Yes,
+        ) == YesNo::Yes;
+        let mut project = oxipaint.editor.to_project();
+        project.tool_name = Some(oxipaint.tools[oxipaint.selected_tool].name());
+        project.colors = Some((
+            oxipaint.draw_context.primary_color,
+            oxipaint.draw_context.secondary_color,
+        ));
+        let history = if include_history {
+            Some(oxipaint.editor.history())
+        } else {
+            None
+        };
+        project::save(path, &project, history)?;
+        Ok(true)
+    }
+
+    pub fn open(oxipaint: &mut OxiPaint) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = tinyfiledialogs::open_file_dialog(
+            "Open file",
+            "",
+            Some((&open_patterns(), "Images and OxiPaint projects")),
+        ) {
+            open_path(oxipaint, Path::new(&path))?;
+        } else {
+            println!("Opening cancelled");
+        }
+        Ok(())
+    }
+
+    pub fn open_path(oxipaint: &mut OxiPaint, path: &Path) -> Result<(), Box<dyn Error>> {
+        let result = if project::is_project_path(path) {
+            let budget = oxipaint.editor.history_budget();
+            let project::LoadedProject {
+                project,
+                history,
+                history_error,
+            } = project::load(path, budget)?;
+            if let Some(e) = history_error {
+                println!(
+                    "Warning: the history of {} has been dropped: {}",
+                    path.display(),
+                    e
+                );
+            }
+            if let Some((primary, secondary)) = project.colors {
+                oxipaint.draw_context.primary_color = primary;
+                oxipaint.draw_context.secondary_color = secondary;
+            }
+            let tool_name = project.tool_name.clone();
+            let result = oxipaint.editor.load_project(project, history);
+            let tool_index = oxipaint
+                .tools
+                .iter()
+                .position(|tool| Some(tool.name()) == tool_name);
+            if let (Ok(_), Some(index)) = (&result, tool_index) {
+                oxipaint.select_tool(index);
+            }
+            result
+        } else {
+            let image = image_io::load(path)?;
+            oxipaint.editor.load_image(&image)
+        };
+        match result {
+            Ok(_) => {
+                println!("Opened {}", path.display());
+                oxipaint.enqueue_redraw();
+            }
+            Err(_) => {
+                println!("Cannot open a file because a drawing action is in progress");
+            }
+        }
+        Ok(())
+    }
+
+    fn open_patterns() -> Vec<&'static str> {
+        lazy_static! {
+            static ref PATTERNS: Vec<String> = image_io::Format::ALL
+                .iter()
+                .flat_map(|format| format.extensions())
+                .chain(std::iter::once(&project::EXTENSION))
+                .map(|extension| format!("*.{}", extension))
+                .collect();
+        }
+        PATTERNS.iter().map(String::as_str).collect()
+    }
+
     pub fn catch(
         func: impl Sync + Fn(&mut OxiPaint) -> Result<(), Box<dyn Error>> + 'static,
     ) -> HotkeyCallback {
@@ -290,38 +772,32 @@ impl HotkeyAction {
     }
 }
 
-lazy_static! {
-    pub static ref HOTKEYS: Vec<(KeyWithMod, HotkeyAction)> = {
-        vec![
-            (
-                KeyModifier::new().ctrl().key(Keycode::Z),
-                HotkeyAction::new(Some(Box::new(hotkey::handle_undo)), None),
-            ),
-            (
-                KeyModifier::new().ctrl().key(Keycode::Y),
-                HotkeyAction::new(Some(Box::new(hotkey::handle_redo)), None),
-            ),
-            (
-                KeyModifier::new().key(Keycode::Space),
-                HotkeyAction::new(
-                    Some(Box::new(|oxi| oxi.start_scrolling())),
-                    Some(Box::new(|oxi| oxi.stop_scrolling())),
-                ),
-            ),
-            (
-                KeyModifier::new().ctrl().key(Keycode::S),
-                HotkeyAction::new(Some(hotkey::catch(Box::new(hotkey::save))), None),
-            ),
-        ]
-    };
+fn default_keymap() -> Keymap {
+    let actions = ACTIONS.iter().map(|action| action.name.clone()).collect();
+    let defaults = HOTKEYS
+        .iter()
+        .map(|(key, name)| Binding::global(*key, name))
+        .collect();
+    let tools = tools::list().iter().map(|tool| tool.name()).collect();
+    Keymap::new(actions, tools, defaults)
 }
 
-fn handle_hotkeys(oxipaint: &mut OxiPaint, key: KeyWithMod, event: PressOrRelease) {
-    for (pattern, action) in HOTKEYS.iter() {
-        if pattern == &key {
-            action.execute(event, oxipaint);
-            break;
-        }
+fn run_hotkey_action(oxipaint: &mut OxiPaint, name: &str, event: PressOrRelease) {
+    if let Some(action) = action::find(name) {
+        action.callbacks.execute(event, oxipaint);
+    }
+}
+
+fn handle_key_press(oxipaint: &mut OxiPaint, key: KeyWithMod) {
+    let tool_name = oxipaint.tools[oxipaint.selected_tool].name();
+    if let Some(name) = oxipaint.keymap.press(key, &tool_name) {
+        run_hotkey_action(oxipaint, &name, PressOrRelease::Press);
+    }
+}
+
+fn handle_key_release(oxipaint: &mut OxiPaint, key: Keycode) {
+    for name in oxipaint.keymap.release(key) {
+        run_hotkey_action(oxipaint, &name, PressOrRelease::Release);
     }
 }
 
@@ -332,18 +808,37 @@ pub struct OxiPaint {
     selected_tool: usize,
     editor: Editor,
     state: OxiPaintState,
-    overlay: Option<Box<dyn Overlay>>,
+    /// Overlays from the bottom to the top.
+    overlays: Vec<Box<dyn Overlay>>,
+    toolbar: Toolbar,
+    history_panel: HistoryPanel,
+    status_bar: StatusBar,
+    clipboard: Option<PixelBuffer>,
+    keymap: Keymap,
 }
 
 impl OxiPaint {
-    pub fn new() -> Result<OxiPaint, SdlError> {
-        let sdl_app = SdlApp::new()?;
+    pub fn new(sdl_app: SdlApp, width: u32, height: u32) -> Result<OxiPaint, SdlError> {
         let draw_context = DrawContext::default();
         let tools = tools::list();
         assert!(!tools.is_empty());
         let selected_tool = 0;
-        let editor = Editor::new(800, 600, Rc::clone(&sdl_app.sdl_canvas));
+        let toolbar = Toolbar::new(tools.iter().map(|tool| tool.name()).collect());
+        let mut editor = Editor::new(width, height, Rc::clone(&sdl_app.sdl_canvas));
+        editor.set_tool_name(tools[selected_tool].name());
         let state = OxiPaintState::default();
+        let mut keymap = default_keymap();
+        for (first, second) in keymap.conflicts() {
+            println!(
+                "Warning: {} and {} are both bound to {}",
+                first.action,
+                second.action,
+                keymap::format_sequence(&first.sequence)
+            );
+        }
+        for problem in keymap.load_user_config() {
+            println!("Warning: {}", problem);
+        }
 
         Ok(OxiPaint {
             sdl_app,
@@ -352,16 +847,18 @@ impl OxiPaint {
             selected_tool,
             editor,
             state,
-            overlay: None,
+            overlays: Vec::new(),
+            toolbar,
+            history_panel: HistoryPanel::new(),
+            status_bar: StatusBar::new(),
+            clipboard: None,
+            keymap,
         })
     }
 
     fn handle_event(&mut self, event: Event) {
-        if let Some(mut overlay) = self.overlay.take() {
-            match overlay.handle_event(&event) {
-                EventResponse::Close => (),
-                EventResponse::Retain => self.overlay = Some(overlay),
-            }
+        if self.dispatch_to_overlays(&event) {
+            return;
         }
 
         match event {
@@ -377,8 +874,27 @@ impl OxiPaint {
             Event::MouseButtonDown {
                 x, y, mouse_btn, ..
             } => {
-                self.update_cursor_position(Some(Point::new(x as u32, y as u32)));
-                self.handle_mouse_button_press(mouse_btn);
+                let (screen_width, screen_height) = self.get_screen_size();
+                if self.status_bar.contains(x, y, screen_width, screen_height) {
+                    // The status bar only shows information.
+                } else if self.toolbar.contains(x, y) {
+                    if let Some(index) = self.toolbar.hit_test(x, y) {
+                        self.select_tool(index);
+                    }
+                } else if self
+                    .history_panel
+                    .contains(x, y, screen_width, screen_height)
+                {
+                    let node = self
+                        .history_panel
+                        .hit_test(x, y, screen_width, screen_height);
+                    if let Some(node) = node {
+                        hotkey::jump_in_history(self, node);
+                    }
+                } else {
+                    self.update_cursor_position(Some(Point::new(x as u32, y as u32)));
+                    self.handle_mouse_button_press(mouse_btn);
+                }
             }
             Event::MouseButtonUp {
                 x, y, mouse_btn, ..
@@ -391,16 +907,16 @@ impl OxiPaint {
                 keymod: sdl_keymod,
                 ..
             } => {
-                let key_with_mod = KeyWithMod::new(key, sdl_keymod.into());
-                handle_hotkeys(self, key_with_mod, PressOrRelease::Press);
+                self.update_keymod(sdl_keymod);
+                handle_key_press(self, KeyWithMod::new(key, sdl_keymod.into()));
             }
             Event::KeyUp {
                 keycode: Some(key),
                 keymod: sdl_keymod,
                 ..
             } => {
-                let key_with_mod = KeyWithMod::new(key, sdl_keymod.into());
-                handle_hotkeys(self, key_with_mod, PressOrRelease::Release);
+                self.update_keymod(sdl_keymod);
+                handle_key_release(self, key);
             }
             Event::Window { win_event, .. } => match win_event {
                 WindowEvent::Leave => {
@@ -417,7 +933,7 @@ impl OxiPaint {
 
                 if let Some(new_scale) = self.editor.scale_up(stationary_point) {
                     println!("Scale increased to {}", new_scale);
-                    self.set_overlay(ZoomOverlay { zoom: new_scale });
+                    self.push_overlay(ZoomOverlay { zoom: new_scale });
                     self.enqueue_redraw();
                 } else {
                     println!("Failed to scale up");
@@ -431,7 +947,7 @@ impl OxiPaint {
 
                 if let Some(new_scale) = self.editor.scale_down(stationary_point) {
                     println!("Scale decreased to {}", new_scale);
-                    self.set_overlay(ZoomOverlay { zoom: new_scale });
+                    self.push_overlay(ZoomOverlay { zoom: new_scale });
                     self.enqueue_redraw();
                 } else {
                     println!("Failed to scale down");
@@ -449,7 +965,7 @@ impl OxiPaint {
         if self.can_draw() {
             let tool = self.tools[self.selected_tool].as_mut();
             if let Redraw::Do =
-                tool.on_mouse_button_press(button, &self.draw_context, &mut self.editor)
+                tool.on_mouse_button_press(button, &mut self.draw_context, &mut self.editor)
             {
                 self.enqueue_redraw();
             }
@@ -460,7 +976,7 @@ impl OxiPaint {
         if self.can_draw() {
             let tool = self.tools[self.selected_tool].as_mut();
             if let Redraw::Do =
-                tool.on_mouse_button_release(button, &self.draw_context, &mut self.editor)
+                tool.on_mouse_button_release(button, &mut self.draw_context, &mut self.editor)
             {
                 self.enqueue_redraw();
             }
@@ -478,7 +994,56 @@ impl OxiPaint {
             }
         } else if self.can_draw() {
             let tool = self.tools[self.selected_tool].as_mut();
-            if let Redraw::Do = tool.on_cursor_move(&self.draw_context, &mut self.editor) {
+            if let Redraw::Do = tool.on_cursor_move(&mut self.draw_context, &mut self.editor) {
+                self.enqueue_redraw();
+            }
+        }
+    }
+
+    fn open_command_palette(&mut self) {
+        let tool_name = self.tools[self.selected_tool].name();
+        let commands = ACTIONS
+            .iter()
+            // Actions which run while a key is held cannot be run from the palette.
+            .filter(|action| !action.is_held())
+            .map(|action| {
+                let shortcuts: Vec<String> = self
+                    .keymap
+                    .sequences_of(&action.name, &tool_name)
+                    .into_iter()
+                    .map(keymap::format_sequence)
+                    .collect();
+                PaletteEntry {
+                    action: action.name.clone(),
+                    title: action.title.clone(),
+                    shortcut: shortcuts.join(", "),
+                }
+            })
+            .collect();
+        self.push_overlay(CommandPalette::new(commands));
+        self.enqueue_redraw();
+    }
+
+    fn select_tool(&mut self, index: usize) {
+        self.editor.commit_selection();
+        if self.editor.is_in_transaction() {
+            println!("Cannot switch tools because a drawing action is in progress");
+            return;
+        }
+        self.selected_tool = index;
+        self.editor.set_tool_name(self.tools[index].name());
+        println!("Selected tool: {}", self.tools[index].name());
+        self.enqueue_redraw();
+    }
+
+    fn update_keymod(&mut self, keymod: Mod) {
+        if self.draw_context.keymod == keymod {
+            return;
+        }
+        self.draw_context.keymod = keymod;
+        if self.can_draw() {
+            let tool = self.tools[self.selected_tool].as_mut();
+            if let Redraw::Do = tool.on_modifiers_change(&mut self.draw_context, &mut self.editor) {
                 self.enqueue_redraw();
             }
         }
@@ -564,6 +1129,9 @@ impl OxiPaint {
 
     pub fn run(mut self) {
         while !self.should_terminate() {
+            if self.status_bar.update(self.status()) {
+                self.enqueue_redraw();
+            }
             if self.should_redraw() {
                 self.sdl_app
                     .sdl_canvas
@@ -571,7 +1139,15 @@ impl OxiPaint {
                     .set_draw_color(Color::BLACK);
                 self.sdl_app.sdl_canvas.borrow_mut().clear();
                 self.editor.draw();
-                if let Some(overlay) = &mut self.overlay {
+                // TODO: maybe use proper error handling?
+                self.toolbar
+                    .draw(&mut self.sdl_app, self.selected_tool)
+                    .unwrap();
+                self.history_panel
+                    .draw(&mut self.sdl_app, self.editor.history())
+                    .unwrap();
+                self.status_bar.draw(&mut self.sdl_app).unwrap();
+                for overlay in &mut self.overlays {
                     // TODO: maybe use proper error handling?
                     overlay.draw(&mut self.sdl_app).unwrap();
                 }
@@ -579,17 +1155,73 @@ impl OxiPaint {
                 self.redrawn();
             }
 
-            let event = self.sdl_app.event_pump.wait_event();
+            let event = if self.editor.selection().is_some() {
+                let timeout = Self::MARCHING_ANTS_INTERVAL_MS;
+                match self.sdl_app.event_pump.wait_event_timeout(timeout) {
+                    Some(event) => event,
+                    None => {
+                        self.editor.advance_marching_ants();
+                        self.enqueue_redraw();
+                        continue;
+                    }
+                }
+            } else {
+                self.sdl_app.event_pump.wait_event()
+            };
             self.handle_event(event);
         }
     }
 
+    const MARCHING_ANTS_INTERVAL_MS: u32 = 150;
+
+    fn status(&self) -> Status {
+        let canvas = self.editor.canvas();
+        let cursor = self
+            .draw_context
+            .cursor_position
+            .point()
+            .map(|point| (point.x.floor() as i64, point.y.floor() as i64));
+        let color = match self.draw_context.cursor_position {
+            TranslatedPoint::WithinCanvas(point) => {
+                canvas.try_get_at(point.x as u32, point.y as u32)
+            }
+            _ => None,
+        };
+        Status {
+            cursor,
+            color,
+            canvas_size: (canvas.width(), canvas.height()),
+            scale: self.editor.scale(),
+            tool_name: self.tools[self.selected_tool].name(),
+            unsaved: self.editor.has_unsaved_changes(),
+        }
+    }
+
     fn can_draw(&self) -> bool {
         !self.is_scrolling()
     }
 
-    fn set_overlay(&mut self, overlay: impl Overlay + 'static) {
-        self.overlay = Some(Box::new(overlay));
+    fn show_zoom(&mut self, zoom: Scale) {
+        println!("Scale set to {}", zoom);
+        self.push_overlay(ZoomOverlay { zoom });
+        self.enqueue_redraw();
+    }
+
+    fn push_overlay(&mut self, overlay: impl Overlay + 'static) {
+        self.overlays.push(Box::new(overlay));
+    }
+
+    /// Passes an event to the overlays and runs the actions they ask for. Returns whether the
+    /// event has been consumed.
+    fn dispatch_to_overlays(&mut self, event: &Event) -> bool {
+        let dispatched = overlay::dispatch(&mut self.overlays, event);
+        if dispatched.redraw {
+            self.enqueue_redraw();
+        }
+        for action in dispatched.actions {
+            run_hotkey_action(self, &action, PressOrRelease::Press);
+        }
+        dispatched.consumed
     }
 }
 
@@ -627,7 +1259,40 @@ fn should_redraw_on(event: &Event) -> bool {
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let oxipaint = OxiPaint::new()?;
-    oxipaint.run();
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    match command {
+        Command::Help => print!("{}", cli::USAGE),
+        Command::PrintKeybindings => print!("{}", default_keymap().to_config()),
+        Command::Batch(job) => {
+            if let Err(e) = job.run() {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Command::Interactive {
+            path,
+            size,
+            history_budget,
+        } => {
+            let (width, height) = size.unwrap_or((800, 600));
+            let sdl_app = SdlApp::new()?;
+            if let Err(e) = cli::check_canvas_size((width, height), sdl_app.max_texture_size()) {
+                eprintln!("{}\n\n{}", e, cli::USAGE);
+                process::exit(2);
+            }
+            let mut oxipaint = OxiPaint::new(sdl_app, width, height)?;
+            oxipaint.editor.set_history_budget(history_budget);
+            if let Some(path) = path {
+                hotkey::open_path(&mut oxipaint, &path)?;
+            }
+            oxipaint.run();
+        }
+    }
     Ok(())
 }
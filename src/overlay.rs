@@ -3,14 +3,81 @@ use sdl2::event::Event;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum EventResponse {
+    /// Remove the overlay and let the event through.
     Close,
+    /// Keep the overlay and let the event through.
     Retain,
+    /// Keep the overlay and stop the event from reaching the overlays below and the editor.
+    Consumed,
 }
 
 pub trait Overlay {
     fn handle_event(&mut self, event: &Event) -> EventResponse;
     fn draw(&mut self, sdl_app: &mut SdlApp) -> Result<(), SdlError>;
+
+    /// Modal overlays consume all the input while they are shown.
+    fn is_modal(&self) -> bool {
+        false
+    }
+
+    /// The name of an action to run after the overlay has handled an event.
+    fn take_action(&mut self) -> Option<String> {
+        None
+    }
+}
+
+/// Whether an event is user input which overlays may consume. Releases are not, so that
+/// nothing started before an overlay has been opened can get stuck.
+pub fn is_consumable(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::KeyDown { .. }
+            | Event::TextInput { .. }
+            | Event::TextEditing { .. }
+            | Event::MouseButtonDown { .. }
+            | Event::MouseMotion { .. }
+            | Event::MouseWheel { .. }
+    )
+}
+
+/// What came of passing an event to the overlays.
+pub struct Dispatched {
+    /// Whether an overlay has consumed the event, so that it must not reach the editor.
+    pub consumed: bool,
+    pub redraw: bool,
+    /// The actions the overlays have asked for, to be run by the caller.
+    pub actions: Vec<String>,
+}
+
+/// Passes an event to the overlays from the top one down, until one of them consumes it, and
+/// removes the overlays which have closed.
+pub fn dispatch(overlays: &mut Vec<Box<dyn Overlay>>, event: &Event) -> Dispatched {
+    let consumable = is_consumable(event);
+    let mut dispatched = Dispatched {
+        consumed: false,
+        redraw: false,
+        actions: Vec::new(),
+    };
+    for index in (0..overlays.len()).rev() {
+        let overlay = &mut overlays[index];
+        let response = overlay.handle_event(event);
+        dispatched.actions.extend(overlay.take_action());
+        if consumable && (overlay.is_modal() || response == EventResponse::Consumed) {
+            dispatched.consumed = true;
+        }
+        if response == EventResponse::Close {
+            overlays.remove(index);
+        }
+        if response != EventResponse::Retain {
+            dispatched.redraw = true;
+        }
+        if dispatched.consumed {
+            break;
+        }
+    }
+    dispatched
 }
 
 pub trait SimpleOverlay {
@@ -44,3 +111,138 @@ impl<T: SimpleOverlay> Overlay for T {
         self.draw(sdl_app, inner_rect)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::keyboard::{Keycode, Mod};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// An overlay which answers every event the same way and logs the events it sees.
+    struct Recorder {
+        name: &'static str,
+        modal: bool,
+        response: EventResponse,
+        action: Option<String>,
+        log: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    impl Overlay for Recorder {
+        fn handle_event(&mut self, _event: &Event) -> EventResponse {
+            self.log.borrow_mut().push(self.name);
+            self.response
+        }
+
+        fn draw(&mut self, _sdl_app: &mut SdlApp) -> Result<(), SdlError> {
+            Ok(())
+        }
+
+        fn is_modal(&self) -> bool {
+            self.modal
+        }
+
+        fn take_action(&mut self) -> Option<String> {
+            self.action.take()
+        }
+    }
+
+    fn recorder(
+        log: &Rc<RefCell<Vec<&'static str>>>,
+        name: &'static str,
+        modal: bool,
+        response: EventResponse,
+    ) -> Box<dyn Overlay> {
+        Box::new(Recorder {
+            name,
+            modal,
+            response,
+            action: None,
+            log: Rc::clone(log),
+        })
+    }
+
+    fn key_down() -> Event {
+        Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(Keycode::A),
+            scancode: None,
+            keymod: Mod::empty(),
+            repeat: false,
+        }
+    }
+
+    fn key_up() -> Event {
+        Event::KeyUp {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(Keycode::A),
+            scancode: None,
+            keymod: Mod::empty(),
+            repeat: false,
+        }
+    }
+
+    #[test]
+    fn modal_overlay_consumes_input() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut overlays = vec![
+            recorder(&log, "below", false, EventResponse::Retain),
+            recorder(&log, "modal", true, EventResponse::Retain),
+        ];
+
+        let dispatched = dispatch(&mut overlays, &key_down());
+        assert!(dispatched.consumed);
+        assert!(!dispatched.redraw);
+        assert_eq!(*log.borrow(), ["modal"]);
+
+        // Releases pass through every overlay and reach the editor.
+        log.borrow_mut().clear();
+        let dispatched = dispatch(&mut overlays, &key_up());
+        assert!(!dispatched.consumed);
+        assert_eq!(*log.borrow(), ["modal", "below"]);
+        assert_eq!(overlays.len(), 2);
+    }
+
+    #[test]
+    fn pass_input_through() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut overlays = vec![
+            recorder(&log, "consuming", false, EventResponse::Consumed),
+            recorder(&log, "closing", false, EventResponse::Close),
+            recorder(&log, "retaining", false, EventResponse::Retain),
+        ];
+
+        let dispatched = dispatch(&mut overlays, &key_down());
+        assert!(dispatched.consumed);
+        assert!(dispatched.redraw);
+        assert_eq!(*log.borrow(), ["retaining", "closing", "consuming"]);
+        assert_eq!(overlays.len(), 2);
+
+        log.borrow_mut().clear();
+        let dispatched = dispatch(&mut overlays, &key_up());
+        assert!(!dispatched.consumed);
+        assert_eq!(*log.borrow(), ["retaining", "consuming"]);
+    }
+
+    #[test]
+    fn collect_actions() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut overlays = vec![
+            recorder(&log, "below", false, EventResponse::Retain),
+            Box::new(Recorder {
+                name: "palette",
+                modal: true,
+                response: EventResponse::Close,
+                action: Some("zoom_in".to_owned()),
+                log: Rc::clone(&log),
+            }) as Box<dyn Overlay>,
+        ];
+        let dispatched = dispatch(&mut overlays, &key_down());
+        assert!(dispatched.consumed);
+        assert_eq!(dispatched.actions, ["zoom_in"]);
+        assert_eq!(*log.borrow(), ["palette"]);
+        assert_eq!(overlays.len(), 1);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::canvas::Canvas;
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec;
+use sdl2::rect::Rect;
+use std::mem;
+
+/// The difference between two versions of a rectangle of layer pixels, stored as their
+/// compressed XOR. XOR is its own inverse, so the same patch both undoes and redoes a change.
+pub struct Patch {
+    rect: Rect,
+    compressed: Vec<u8>,
+}
+
+impl Patch {
+    /// Creates a patch which turns `before` into `after` within the given rectangle. Both
+    /// iterators yield the BGRA rows of the rectangle from top to bottom.
+    pub fn between<'a>(
+        rect: Rect,
+        before: impl Iterator<Item = &'a [u8]>,
+        after: impl Iterator<Item = &'a [u8]>,
+    ) -> Patch {
+        let mut xor =
+            Vec::with_capacity(rect.width() as usize * rect.height() as usize * Canvas::BPP);
+        for (before_row, after_row) in before.zip(after) {
+            xor.extend(before_row.iter().zip(after_row).map(|(b, a)| b ^ a));
+        }
+        let mut compressed = compress_to_vec(&xor, Self::COMPRESSION_LEVEL);
+        compressed.shrink_to_fit();
+        Patch { rect, compressed }
+    }
+
+    /// Recreates a patch from its compressed representation, as returned by `compressed`.
+    pub fn from_compressed(rect: Rect, compressed: Vec<u8>) -> Patch {
+        Patch { rect, compressed }
+    }
+
+    pub fn compressed(&self) -> &[u8] {
+        &self.compressed
+    }
+
+    /// Applies the patch to a buffer of BGRA rows which are `width` pixels long. Applying
+    /// it twice leaves the buffer unchanged.
+    pub fn apply(&self, data: &mut [u8], width: u32) {
+        let xor = decompress_to_vec(&self.compressed).expect("History contains a corrupted patch");
+        let row_len = self.rect.width() as usize * Canvas::BPP;
+        for (y, xor_row) in (self.rect.top()..).zip(xor.chunks_exact(row_len)) {
+            let offset = Self::offset(self.rect.left(), y, width);
+            for (byte, mask) in data[offset..offset + row_len].iter_mut().zip(xor_row) {
+                *byte ^= mask;
+            }
+        }
+    }
+
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    /// The number of bytes taken by the patch, including its own size.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<Patch>() + self.compressed.capacity()
+    }
+
+    /// Iterates over the rows of the given rectangle within a buffer of BGRA rows which are
+    /// `width` pixels long.
+    pub fn rows(data: &[u8], width: u32, rect: Rect) -> impl Iterator<Item = &[u8]> + Clone {
+        let row_len = rect.width() as usize * Canvas::BPP;
+        (rect.top()..rect.bottom()).map(move |y| {
+            let offset = Self::offset(rect.left(), y, width);
+            &data[offset..offset + row_len]
+        })
+    }
+
+    fn offset(x: i32, y: i32, width: u32) -> usize {
+        (y as usize * width as usize + x as usize) * Canvas::BPP
+    }
+
+    /// Favours speed, since patches are created after every stroke.
+    const COMPRESSION_LEVEL: u8 = 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 5;
+    const HEIGHT: u32 = 4;
+
+    fn data(seed: u8) -> Vec<u8> {
+        (0..WIDTH * HEIGHT * Canvas::BPP as u32)
+            .map(|i| (i as u8).wrapping_mul(seed))
+            .collect()
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let before = data(3);
+        let mut after = before.clone();
+        let rect = Rect::new(1, 1, 3, 2);
+        for row in 1..3 {
+            for byte in 4..16 {
+                after[row * WIDTH as usize * Canvas::BPP + byte] ^= 0xa5;
+            }
+        }
+        let patch = Patch::between(
+            rect,
+            Patch::rows(&before, WIDTH, rect),
+            Patch::rows(&after, WIDTH, rect),
+        );
+        assert_eq!(patch.rect(), rect);
+
+        let mut buffer = after.clone();
+        patch.apply(&mut buffer, WIDTH);
+        assert_eq!(buffer, before);
+        patch.apply(&mut buffer, WIDTH);
+        assert_eq!(buffer, after);
+
+        // The compressed form is all that is needed to recreate the patch.
+        let copy = Patch::from_compressed(rect, patch.compressed().to_vec());
+        copy.apply(&mut buffer, WIDTH);
+        assert_eq!(buffer, before);
+    }
+
+    #[test]
+    fn memory_usage() {
+        let before = vec![0; 64 * 64 * Canvas::BPP];
+        let mut after = before.clone();
+        after[0] = 1;
+        let rect = Rect::new(0, 0, 64, 64);
+        let patch = Patch::between(
+            rect,
+            Patch::rows(&before, 64, rect),
+            Patch::rows(&after, 64, rect),
+        );
+        assert_eq!(
+            patch.memory_usage(),
+            mem::size_of::<Patch>() + patch.compressed().len()
+        );
+        // A small change to a large tile compresses well.
+        assert!(patch.memory_usage() < before.len() / 16);
+    }
+}
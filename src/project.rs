@@ -0,0 +1,952 @@
+//! The native OxiPaint project format.
+//!
+//! A project file starts with the `OXIPAINT` magic and a little-endian `u32` format version,
+//! followed by a sequence of chunks. Each chunk consists of a four-byte tag, a `u32` length
+//! and the payload. As in PNG, chunks whose tag starts with an uppercase letter are critical:
+//! a reader which does not know such a chunk must give up. Unknown ancillary chunks (with a
+//! lowercase first letter) are skipped, and so are unknown trailing bytes of known chunks,
+//! which allows later versions to append new fields.
+
+use crate::canvas::Canvas;
+use crate::geometry::{Point, Scale};
+use crate::history::{Diff, DiffDirection, History, NodeId};
+use crate::image_io::{self, RgbaImage};
+use crate::layer::{composite_row, Layer, LayerId};
+use crate::patch::Patch;
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Everything which is saved to a project file, except for the history.
+pub struct Project {
+    pub width: u32,
+    pub height: u32,
+    /// Layers from the bottom to the top.
+    pub layers: Vec<Layer>,
+    pub active_layer: usize,
+    pub tool_name: Option<String>,
+    /// The primary and the secondary colors.
+    pub colors: Option<(Color, Color)>,
+    /// The scale and the center of the view.
+    pub view: Option<(Scale, Point)>,
+}
+
+impl Project {
+    /// Blends the visible layers together with the same code as the canvas, without needing
+    /// one.
+    pub fn flatten(&self) -> Result<RgbaImage, Box<dyn Error>> {
+        let mut data = vec![0; image_io::checked_data_size(self.width, self.height)?];
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            composite_row(&mut data, &layer.data, layer);
+        }
+        for pixel in data.chunks_mut(Canvas::BPP) {
+            pixel.swap(0, 2);
+        }
+        Ok(RgbaImage {
+            width: self.width,
+            height: self.height,
+            data,
+        })
+    }
+}
+
+/// The result of loading a project file.
+pub struct LoadedProject {
+    pub project: Project,
+    /// The saved history, if the file has one and it could be restored.
+    pub history: Option<History>,
+    /// Why the saved history has been dropped, if it has been. The project itself is still
+    /// usable then.
+    pub history_error: Option<Box<dyn Error>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProjectError(String);
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(formatter, "Invalid project file: {}", self.0)
+    }
+}
+
+impl Error for ProjectError {}
+
+pub const EXTENSION: &str = "oxp";
+
+pub fn is_project_path(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some(e) if e.eq_ignore_ascii_case(EXTENSION)
+    )
+}
+
+const MAGIC: &[u8; 8] = b"OXIPAINT";
+const FORMAT_VERSION: u32 = 1;
+
+const HEADER_CHUNK: &[u8; 4] = b"HEAD";
+const LAYER_CHUNK: &[u8; 4] = b"LAYR";
+const TOOL_CHUNK: &[u8; 4] = b"tOOL";
+const COLORS_CHUNK: &[u8; 4] = b"cOLR";
+const VIEW_CHUNK: &[u8; 4] = b"vIEW";
+const HISTORY_CHUNK: &[u8; 4] = b"hIST";
+
+pub fn save(
+    path: &Path,
+    project: &Project,
+    history: Option<&History>,
+) -> Result<(), Box<dyn Error>> {
+    let mut file = MAGIC.to_vec();
+    file.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+
+    let mut header = Writer::new();
+    header.u32(project.width);
+    header.u32(project.height);
+    header.u32(project.active_layer as u32);
+    header.finish_chunk(HEADER_CHUNK, &mut file);
+
+    for layer in &project.layers {
+        let mut chunk = Writer::new();
+        chunk.layer(layer);
+        chunk.finish_chunk(LAYER_CHUNK, &mut file);
+    }
+
+    if let Some(tool_name) = &project.tool_name {
+        let mut chunk = Writer::new();
+        chunk.string(tool_name);
+        chunk.finish_chunk(TOOL_CHUNK, &mut file);
+    }
+
+    if let Some((primary, secondary)) = project.colors {
+        let mut chunk = Writer::new();
+        chunk.color(primary);
+        chunk.color(secondary);
+        chunk.finish_chunk(COLORS_CHUNK, &mut file);
+    }
+
+    if let Some((scale, center)) = project.view {
+        let mut chunk = Writer::new();
+        chunk.scale(scale);
+        chunk.f64(center.x);
+        chunk.f64(center.y);
+        chunk.finish_chunk(VIEW_CHUNK, &mut file);
+    }
+
+    if let Some(history) = history {
+        let mut chunk = Writer::new();
+        chunk.history(history);
+        chunk.finish_chunk(HISTORY_CHUNK, &mut file);
+    }
+
+    fs::write(path, file)?;
+    Ok(())
+}
+
+/// Loads a project. The history is only restored if the file contains one, using the given
+/// memory budget. A history which is invalid or does not fit into the budget is dropped
+/// rather than failing the whole load.
+pub fn load(path: &Path, history_budget: usize) -> Result<LoadedProject, Box<dyn Error>> {
+    read(path, Some(history_budget))
+}
+
+/// Loads a project, skipping its history, which is neither decoded nor checked.
+pub fn load_without_history(path: &Path) -> Result<Project, Box<dyn Error>> {
+    Ok(read(path, None)?.project)
+}
+
+/// Loads a project, and its history if a budget for it is given.
+fn read(path: &Path, history_budget: Option<usize>) -> Result<LoadedProject, Box<dyn Error>> {
+    let file = fs::read(path)?;
+    let mut reader = Reader::new(&file);
+    if reader.bytes(MAGIC.len())? != MAGIC {
+        return Err(error("not an OxiPaint project"));
+    }
+    let version = reader.u32()?;
+    if version == 0 {
+        return Err(error("invalid format version"));
+    }
+
+    let mut header = None;
+    let mut project = Project {
+        width: 0,
+        height: 0,
+        layers: Vec::new(),
+        active_layer: 0,
+        tool_name: None,
+        colors: None,
+        view: None,
+    };
+    // The history is only read once the layers are known, so that it can be checked against
+    // them.
+    let mut history_chunk = None;
+    while !reader.is_empty() {
+        let tag = reader.bytes(4)?;
+        let length = reader.u32()? as usize;
+        let mut chunk = Reader::new(reader.bytes(length)?);
+        match tag {
+            t if t == HEADER_CHUNK => {
+                header = Some((chunk.u32()?, chunk.u32()?, chunk.u32()? as usize));
+            }
+            t if t == LAYER_CHUNK => project.layers.push(chunk.layer()?),
+            t if t == TOOL_CHUNK => project.tool_name = Some(chunk.string()?),
+            t if t == COLORS_CHUNK => project.colors = Some((chunk.color()?, chunk.color()?)),
+            t if t == VIEW_CHUNK => {
+                project.view = Some((chunk.scale()?, Point::new(chunk.f64()?, chunk.f64()?)));
+            }
+            t if t == HISTORY_CHUNK => {
+                history_chunk = history_budget.map(|budget| (chunk, budget));
+            }
+            t if t[0].is_ascii_uppercase() => {
+                return Err(error(&format!(
+                    "unknown critical chunk {} in a version {} file",
+                    String::from_utf8_lossy(t),
+                    version
+                )));
+            }
+            _ => (),
+        }
+    }
+
+    let (width, height, active_layer) = header.ok_or_else(|| error("missing header"))?;
+    project.width = width;
+    project.height = height;
+    project.active_layer = active_layer;
+    if width == 0 || height == 0 {
+        return Err(error("empty canvas"));
+    }
+    if project.layers.is_empty() || active_layer >= project.layers.len() {
+        return Err(error("invalid layers"));
+    }
+    let data_size = image_io::checked_data_size(width, height)?;
+    if project
+        .layers
+        .iter()
+        .any(|layer| layer.data.len() != data_size)
+    {
+        return Err(error("layer size does not match the canvas size"));
+    }
+    for (index, layer) in project.layers.iter().enumerate() {
+        if project.layers[..index]
+            .iter()
+            .any(|other| other.id == layer.id)
+        {
+            return Err(error("duplicate layer identifiers"));
+        }
+    }
+    let (history, history_error) =
+        match history_chunk.map(|(mut chunk, budget)| chunk.history(&project, budget)) {
+            Some(Ok(history)) => (Some(history), None),
+            Some(Err(e)) => (None, Some(e)),
+            None => (None, None),
+        };
+    Ok(LoadedProject {
+        project,
+        history,
+        history_error,
+    })
+}
+
+fn error(message: &str) -> Box<dyn Error> {
+    Box::new(ProjectError(message.to_owned()))
+}
+
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn new() -> Writer {
+        Writer { buf: Vec::new() }
+    }
+
+    fn finish_chunk(self, tag: &[u8; 4], file: &mut Vec<u8>) {
+        file.extend_from_slice(tag);
+        file.extend_from_slice(&(self.buf.len() as u32).to_le_bytes());
+        file.extend_from_slice(&self.buf);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, string: &str) {
+        self.bytes(string.as_bytes());
+    }
+
+    fn color(&mut self, color: Color) {
+        self.buf
+            .extend_from_slice(&[color.r, color.g, color.b, color.a]);
+    }
+
+    fn scale(&mut self, scale: Scale) {
+        match scale {
+            Scale::Times(factor) => {
+                self.u8(0);
+                self.u32(factor);
+            }
+            Scale::Fraction {
+                numerator,
+                denominator,
+            } => {
+                self.u8(1);
+                self.u32(numerator);
+                self.u32(denominator);
+            }
+        }
+    }
+
+    fn layer(&mut self, layer: &Layer) {
+        self.u32(layer.id.0);
+        self.string(&layer.name);
+        self.u8(layer.visible as u8);
+        self.u8(layer.locked as u8);
+        self.f64(layer.opacity);
+        self.bytes(&compress_to_vec(&layer.data, 6));
+    }
+
+    fn history(&mut self, history: &History) {
+        let ids = history.node_ids();
+        let indices: HashMap<NodeId, u32> = ids
+            .iter()
+            .enumerate()
+            .map(|(index, &id)| (id, index as u32))
+            .collect();
+        self.u32(ids.len() as u32);
+        for &id in &ids {
+            // The root has no parent and no diff.
+            if let (Some(parent), Some(diff)) = (history.parent(id), history.diff(id)) {
+                self.u32(indices[&parent]);
+                self.string(history.label(id));
+                self.diff(diff);
+            }
+        }
+        self.u32(indices[&history.current()]);
+        self.u32(history.checkpoints().len() as u32);
+        for checkpoint in history.checkpoints() {
+            self.u32(indices[&checkpoint.node]);
+            self.string(&checkpoint.name);
+        }
+        // Added after the first version of the format, so it is optional.
+        for &id in &ids {
+            self.u32(
+                history
+                    .active_child(id)
+                    .map_or(NO_NODE, |child| indices[&child]),
+            );
+        }
+    }
+
+    fn diff(&mut self, diff: &Diff) {
+        match diff {
+            Diff::Tiled { layer, tiles } => {
+                self.u8(0);
+                self.u32(layer.0);
+                self.u32(tiles.len() as u32);
+                for patch in tiles {
+                    let rect = patch.rect();
+                    self.i32(rect.x());
+                    self.i32(rect.y());
+                    self.u32(rect.width());
+                    self.u32(rect.height());
+                    self.bytes(patch.compressed());
+                }
+            }
+            Diff::InsertLayer { index, layer } => {
+                self.u8(1);
+                self.u32(*index as u32);
+                self.layer(layer);
+            }
+            Diff::RemoveLayer { index, layer } => {
+                self.u8(2);
+                self.u32(*index as u32);
+                self.layer(layer);
+            }
+            Diff::MoveLayer { from, to } => {
+                self.u8(3);
+                self.u32(*from as u32);
+                self.u32(*to as u32);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        if count > self.data.len() {
+            return Err(error("unexpected end of data"));
+        }
+        let (bytes, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Box<dyn Error>> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, Box<dyn Error>> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, Box<dyn Error>> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    fn sized_bytes(&mut self) -> Result<&'a [u8], Box<dyn Error>> {
+        let length = self.u32()? as usize;
+        self.bytes(length)
+    }
+
+    fn string(&mut self) -> Result<String, Box<dyn Error>> {
+        Ok(String::from_utf8(self.sized_bytes()?.to_vec())?)
+    }
+
+    fn color(&mut self) -> Result<Color, Box<dyn Error>> {
+        let [r, g, b, a] = self.array()?;
+        Ok(Color::RGBA(r, g, b, a))
+    }
+
+    fn scale(&mut self) -> Result<Scale, Box<dyn Error>> {
+        let scale = match self.u8()? {
+            0 => Scale::Times(self.u32()?),
+            1 => Scale::fraction(self.u32()?, self.u32()?),
+            _ => return Err(error("unknown scale kind")),
+        };
+        match scale {
+            Scale::Times(0) | Scale::Fraction { denominator: 0, .. } => Err(error("zero scale")),
+            Scale::Fraction { numerator: 0, .. } => Err(error("zero scale")),
+            Scale::Times(_) if scale.factor() > Scale::MAX.factor() => {
+                Err(error("scale is too large"))
+            }
+            Scale::Fraction {
+                numerator,
+                denominator,
+            } if numerator >= denominator => Err(error("invalid scale")),
+            _ => Ok(scale),
+        }
+    }
+
+    fn layer(&mut self) -> Result<Layer, Box<dyn Error>> {
+        let id = LayerId(self.u32()?);
+        let name = self.string()?;
+        let visible = self.u8()? != 0;
+        let locked = self.u8()? != 0;
+        let opacity = self.f64()?;
+        let data = decompress_to_vec(self.sized_bytes()?)
+            .map_err(|e| error(&format!("corrupted layer data ({:?})", e)))?;
+        let mut layer = Layer::new(id, name, data);
+        layer.visible = visible;
+        layer.locked = locked;
+        layer.opacity = opacity.clamp(0.0, 1.0);
+        Ok(layer)
+    }
+
+    /// Reads a history whose current node matches the given project. Every diff is checked
+    /// against the layers it would be applied to, so that a corrupted history is rejected
+    /// here rather than when it is undone.
+    fn history(
+        &mut self,
+        project: &Project,
+        memory_budget: usize,
+    ) -> Result<History, Box<dyn Error>> {
+        let node_count = self.u32()? as usize;
+        if node_count == 0 {
+            return Err(error("empty history"));
+        }
+        // Nodes are stored with their parents first, so the parents are already known.
+        let mut nodes = Vec::new();
+        for index in 1..node_count {
+            let parent = self.node_index(index)?;
+            let label = self.string()?;
+            let diff = self.diff()?;
+            nodes.push((parent, label, diff));
+        }
+        let current = self.node_index(node_count)?;
+        let mut checkpoints = Vec::new();
+        for _ in 0..self.u32()? {
+            checkpoints.push((self.node_index(node_count)?, self.string()?));
+        }
+        let mut active_children = Vec::new();
+        if !self.is_empty() {
+            for index in 0..node_count {
+                let child = self.u32()?;
+                if child == NO_NODE {
+                    continue;
+                }
+                let child = child as usize;
+                if child == 0 || child >= node_count || nodes[child - 1].0 != index {
+                    return Err(error("history refers to an unknown node"));
+                }
+                active_children.push((index, child));
+            }
+        }
+
+        check_history(project, &nodes, current)?;
+        let memory_usage: usize = nodes.iter().map(|(_, _, diff)| diff.memory_usage()).sum();
+        if memory_usage > memory_budget {
+            // Rebuilding the tree would forget nodes which are still needed to rebuild it.
+            return Err(error(&format!(
+                "the history takes {} MiB, more than the budget of {} MiB",
+                memory_usage >> 20,
+                memory_budget >> 20
+            )));
+        }
+
+        let mut history = History::new(memory_budget);
+        let mut ids = vec![history.current()];
+        for (parent, label, diff) in nodes {
+            history.set_current(ids[parent]);
+            history.record(diff, label);
+            ids.push(history.current());
+        }
+        for (node, name) in checkpoints {
+            history.set_current(ids[node]);
+            history.add_checkpoint(name);
+        }
+        history.set_current(ids[current]);
+        for (node, child) in active_children {
+            history.set_active_child(ids[node], ids[child]);
+        }
+        Ok(history)
+    }
+
+    /// Reads the index of a node, which must be less than `limit`.
+    fn node_index(&mut self, limit: usize) -> Result<usize, Box<dyn Error>> {
+        let index = self.u32()? as usize;
+        if index < limit {
+            Ok(index)
+        } else {
+            Err(error("history refers to an unknown node"))
+        }
+    }
+
+    fn diff(&mut self) -> Result<Diff, Box<dyn Error>> {
+        Ok(match self.u8()? {
+            0 => {
+                let layer = LayerId(self.u32()?);
+                let count = self.u32()?;
+                let mut tiles = Vec::new();
+                for _ in 0..count {
+                    let rect = Rect::new(self.i32()?, self.i32()?, self.u32()?, self.u32()?);
+                    tiles.push(Patch::from_compressed(rect, self.sized_bytes()?.to_vec()));
+                }
+                Diff::Tiled { layer, tiles }
+            }
+            1 => Diff::InsertLayer {
+                index: self.u32()? as usize,
+                layer: Box::new(self.layer()?),
+            },
+            2 => Diff::RemoveLayer {
+                index: self.u32()? as usize,
+                layer: Box::new(self.layer()?),
+            },
+            3 => Diff::MoveLayer {
+                from: self.u32()? as usize,
+                to: self.u32()? as usize,
+            },
+            _ => return Err(error("unknown diff kind")),
+        })
+    }
+}
+
+/// Marks the absence of a node where a node index is expected.
+const NO_NODE: u32 = u32::MAX;
+
+/// Checks that every diff of a history can be applied to the layers it leads from and to.
+/// `nodes` holds the parent index, the label and the diff of every node except the root,
+/// which has the index 0, and the layers of the project are the ones of the current node.
+fn check_history(
+    project: &Project,
+    nodes: &[(usize, String, Diff)],
+    current: usize,
+) -> Result<(), Box<dyn Error>> {
+    let mut children = vec![Vec::new(); nodes.len() + 1];
+    for (index, (parent, _, _)) in nodes.iter().enumerate() {
+        children[*parent].push(index + 1);
+    }
+    // The identifiers of the layers at every node, found by walking the tree from the
+    // current node, whose layers are known.
+    let mut layers = vec![None; nodes.len() + 1];
+    layers[current] = Some(project.layers.iter().map(|layer| layer.id).collect());
+    let mut stack = vec![current];
+    while let Some(index) = stack.pop() {
+        let ids: Vec<LayerId> = layers[index].clone().unwrap();
+        if index > 0 {
+            let (parent, _, diff) = &nodes[index - 1];
+            if layers[*parent].is_none() {
+                let parent_ids = apply_to_layer_ids(&ids, diff, DiffDirection::Reverse, project)?;
+                layers[*parent] = Some(parent_ids);
+                stack.push(*parent);
+            }
+        }
+        for &child in &children[index] {
+            if layers[child].is_none() {
+                let diff = &nodes[child - 1].2;
+                let child_ids = apply_to_layer_ids(&ids, diff, DiffDirection::Normal, project)?;
+                layers[child] = Some(child_ids);
+                stack.push(child);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a diff can be applied to a stack of layers with the given identifiers, the
+/// same way `Canvas::apply_diff` would apply it, and returns the identifiers afterwards.
+fn apply_to_layer_ids(
+    ids: &[LayerId],
+    diff: &Diff,
+    direction: DiffDirection,
+    project: &Project,
+) -> Result<Vec<LayerId>, Box<dyn Error>> {
+    let mut ids = ids.to_vec();
+    let data_size = project.width as usize * project.height as usize * Canvas::BPP;
+    let insert = |ids: &mut Vec<LayerId>, index: usize, layer: &Layer| {
+        if index > ids.len() || ids.contains(&layer.id) || layer.data.len() != data_size {
+            return Err(error("history inserts an invalid layer"));
+        }
+        ids.insert(index, layer.id);
+        Ok(())
+    };
+    let remove = |ids: &mut Vec<LayerId>, index: usize, layer: &Layer| {
+        if ids.len() <= 1 || ids.get(index) != Some(&layer.id) {
+            return Err(error("history removes an invalid layer"));
+        }
+        ids.remove(index);
+        Ok(())
+    };
+    match (diff, &direction) {
+        (Diff::Tiled { layer, tiles }, _) => {
+            if !ids.contains(layer) {
+                return Err(error("history refers to an unknown layer"));
+            }
+            for patch in tiles {
+                let rect = patch.rect();
+                let fits = rect.x() >= 0
+                    && rect.y() >= 0
+                    && rect.x() as u64 + rect.width() as u64 <= project.width as u64
+                    && rect.y() as u64 + rect.height() as u64 <= project.height as u64;
+                let size = rect.width() as usize * rect.height() as usize * Canvas::BPP;
+                if !fits || decompress_to_vec(patch.compressed()).map(|xor| xor.len()) != Ok(size) {
+                    return Err(error("history contains an invalid patch"));
+                }
+            }
+        }
+        (Diff::InsertLayer { index, layer }, &DiffDirection::Normal)
+        | (Diff::RemoveLayer { index, layer }, &DiffDirection::Reverse) => {
+            insert(&mut ids, *index, layer)?
+        }
+        (Diff::InsertLayer { index, layer }, &DiffDirection::Reverse)
+        | (Diff::RemoveLayer { index, layer }, &DiffDirection::Normal) => {
+            remove(&mut ids, *index, layer)?
+        }
+        (Diff::MoveLayer { from, to }, _) => {
+            if *from >= ids.len() || *to >= ids.len() {
+                return Err(error("history moves an invalid layer"));
+            }
+            let (from, to) = match direction {
+                DiffDirection::Normal => (*from, *to),
+                DiffDirection::Reverse => (*to, *from),
+            };
+            let id = ids.remove(from);
+            ids.insert(to, id);
+        }
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    const WIDTH: u32 = 3;
+    const HEIGHT: u32 = 2;
+
+    fn layer(id: u32, value: u8) -> Layer {
+        let data = (0..WIDTH * HEIGHT * 4).map(|i| value ^ i as u8).collect();
+        Layer::new(LayerId(id), format!("Layer {}", id), data)
+    }
+
+    fn project() -> Project {
+        let mut top = layer(1, 0x5a);
+        top.visible = false;
+        top.locked = true;
+        top.opacity = 0.5;
+        Project {
+            width: WIDTH,
+            height: HEIGHT,
+            layers: vec![layer(0, 0xff), top],
+            active_layer: 1,
+            tool_name: Some("Fill".to_owned()),
+            colors: Some((Color::RGBA(1, 2, 3, 4), Color::RGBA(5, 6, 7, 8))),
+            view: Some((Scale::fraction(1, 2), Point::new(1.5, 0.25))),
+        }
+    }
+
+    fn patch(rect: Rect) -> Patch {
+        let xor = vec![0x11; rect.width() as usize * rect.height() as usize * 4];
+        Patch::from_compressed(rect, compress_to_vec(&xor, 1))
+    }
+
+    /// A history with two branches, whose current node has the layers of `project`, and
+    /// where `redo` follows the older branch.
+    fn history() -> History {
+        let mut history = History::new(usize::MAX);
+        let root = history.current();
+        history.record(
+            Diff::InsertLayer {
+                index: 1,
+                layer: Box::new(layer(1, 0)),
+            },
+            "Add layer".to_owned(),
+        );
+        let inserted = history.current();
+        history.record(
+            Diff::Tiled {
+                layer: LayerId(0),
+                tiles: vec![patch(Rect::new(1, 0, 2, 2))],
+            },
+            "Pencil".to_owned(),
+        );
+        history.add_checkpoint("first".to_owned());
+        let older = history.current();
+        history.set_current(inserted);
+        history.record(Diff::MoveLayer { from: 0, to: 1 }, "Move layer".to_owned());
+        history.add_checkpoint("second".to_owned());
+        history.set_current(older);
+        history.undo();
+        assert_eq!(history.active_child(inserted), Some(older));
+        assert_eq!(history.parent(inserted), Some(root));
+        history
+    }
+
+    fn describe(history: &History) -> Vec<String> {
+        let mut lines: Vec<String> = history
+            .node_ids()
+            .into_iter()
+            .map(|id| {
+                let mut diff = Writer::new();
+                if let Some(d) = history.diff(id) {
+                    diff.diff(d);
+                }
+                format!(
+                    "{:?} parent {:?} active child {:?} {} {:?}",
+                    id,
+                    history.parent(id),
+                    history.active_child(id),
+                    history.label(id),
+                    diff.buf
+                )
+            })
+            .collect();
+        lines.push(format!("current {:?}", history.current()));
+        lines.push(format!("checkpoints {:?}", history.checkpoints()));
+        lines
+    }
+
+    fn save_and_load(
+        name: &str,
+        history: Option<&History>,
+        budget: usize,
+    ) -> Result<LoadedProject, Box<dyn Error>> {
+        let path = env::temp_dir().join(format!("oxipaint-{}-{}.oxp", process::id(), name));
+        save(&path, &project(), history).unwrap();
+        let result = load(&path, budget);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn round_trip() {
+        let expected = project();
+        let loaded = save_and_load("round-trip", None, usize::MAX).unwrap();
+        assert!(loaded.history.is_none() && loaded.history_error.is_none());
+        let project = loaded.project;
+        assert_eq!((project.width, project.height), (WIDTH, HEIGHT));
+        assert_eq!(project.active_layer, expected.active_layer);
+        assert_eq!(project.tool_name, expected.tool_name);
+        assert_eq!(project.colors, expected.colors);
+        assert_eq!(project.view, expected.view);
+        assert_eq!(project.layers.len(), expected.layers.len());
+        for (layer, expected) in project.layers.iter().zip(&expected.layers) {
+            assert_eq!(layer.id, expected.id);
+            assert_eq!(layer.name, expected.name);
+            assert_eq!(layer.visible, expected.visible);
+            assert_eq!(layer.locked, expected.locked);
+            assert_eq!(layer.opacity, expected.opacity);
+            assert_eq!(layer.data, expected.data);
+        }
+    }
+
+    #[test]
+    fn reject_invalid_header_and_view() {
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        let mut header = Writer::new();
+        header.u32(u32::MAX);
+        header.u32(u32::MAX);
+        header.u32(0);
+        header.finish_chunk(HEADER_CHUNK, &mut file);
+        let mut chunk = Writer::new();
+        chunk.layer(&layer(0, 0));
+        chunk.finish_chunk(LAYER_CHUNK, &mut file);
+        let path = env::temp_dir().join(format!("oxipaint-{}-header.oxp", process::id()));
+        fs::write(&path, file).unwrap();
+        let result = load(&path, usize::MAX);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+
+        for (index, &scale) in [Scale::Times(u32::MAX), Scale::fraction(3, 2)]
+            .iter()
+            .enumerate()
+        {
+            let mut project = project();
+            project.view = Some((scale, Point::new(0.0, 0.0)));
+            let path =
+                env::temp_dir().join(format!("oxipaint-{}-view{}.oxp", process::id(), index));
+            save(&path, &project, None).unwrap();
+            let result = load(&path, usize::MAX);
+            fs::remove_file(&path).unwrap();
+            assert!(result.is_err(), "{:?} has been accepted", scale);
+        }
+    }
+
+    #[test]
+    fn round_trip_history() {
+        let expected = history();
+        let loaded = save_and_load("history", Some(&expected), usize::MAX).unwrap();
+        assert!(loaded.history_error.is_none());
+        let mut history = loaded.history.unwrap();
+        assert_eq!(describe(&history), describe(&expected));
+        // Redo follows the branch which was active when saving, not the newest one.
+        let branch = history.active_branch();
+        assert_eq!(branch, expected.active_branch());
+        assert!(history.redo().is_some());
+        assert_eq!(history.label(history.current()), "Pencil");
+    }
+
+    fn assert_dropped(name: &str, diff: Diff) {
+        let mut history = History::new(usize::MAX);
+        history.record(
+            Diff::InsertLayer {
+                index: 1,
+                layer: Box::new(layer(1, 0)),
+            },
+            "Add layer".to_owned(),
+        );
+        history.record(diff, "Broken".to_owned());
+        history.undo();
+        assert_history_dropped(save_and_load(name, Some(&history), usize::MAX));
+    }
+
+    /// Checks that the project has been loaded, but without its history.
+    fn assert_history_dropped(result: Result<LoadedProject, Box<dyn Error>>) {
+        let loaded = result.unwrap_or_else(|e| panic!("The project has been rejected: {}", e));
+        assert!(loaded.history.is_none(), "The history has been accepted");
+        let error = loaded.history_error.unwrap();
+        assert!(error.is::<ProjectError>(), "{}", error);
+        assert_eq!(loaded.project.layers.len(), project().layers.len());
+    }
+
+    #[test]
+    fn drop_invalid_history() {
+        let tiled = |layer, rect| Diff::Tiled {
+            layer: LayerId(layer),
+            tiles: vec![patch(rect)],
+        };
+        assert_dropped("outside", tiled(0, Rect::new(2, 1, 2, 2)));
+        assert_dropped("unknown-layer", tiled(7, Rect::new(0, 0, 1, 1)));
+        let short_patch =
+            Patch::from_compressed(Rect::new(0, 0, 2, 2), compress_to_vec(&[0; 4], 1));
+        assert_dropped(
+            "short-patch",
+            Diff::Tiled {
+                layer: LayerId(0),
+                tiles: vec![short_patch],
+            },
+        );
+        assert_dropped("move", Diff::MoveLayer { from: 0, to: 2 });
+        assert_dropped(
+            "insert",
+            Diff::InsertLayer {
+                index: 0,
+                layer: Box::new(Layer::new(LayerId(5), String::new(), vec![0; 4])),
+            },
+        );
+        assert_dropped(
+            "remove",
+            Diff::RemoveLayer {
+                index: 0,
+                layer: Box::new(layer(1, 0)),
+            },
+        );
+    }
+
+    #[test]
+    fn skip_history() {
+        let path = env::temp_dir().join(format!("oxipaint-{}-skip-history.oxp", process::id()));
+        save(&path, &project(), None).unwrap();
+        let mut file = fs::read(&path).unwrap();
+        let mut garbage = Writer::new();
+        garbage.u32(7);
+        garbage.finish_chunk(HISTORY_CHUNK, &mut file);
+        fs::write(&path, file).unwrap();
+        let loaded = load(&path, usize::MAX);
+        let skipped = load_without_history(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(loaded.unwrap().history_error.is_some());
+        assert_eq!(skipped.unwrap().layers.len(), project().layers.len());
+    }
+
+    #[test]
+    fn drop_history_over_budget() {
+        let history = history();
+        assert_history_dropped(save_and_load("budget", Some(&history), 16));
+    }
+}
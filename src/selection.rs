@@ -0,0 +1,86 @@
+use crate::canvas::Canvas;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+/// A rectangular block of pixels, used both for floating selections and for the clipboard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PixelBuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+impl PixelBuffer {
+    /// Copies the given rectangle of the active layer. The rectangle must lie within the canvas.
+    pub fn copy_from(canvas: &Canvas, rect: Rect) -> PixelBuffer {
+        let mut pixels = Vec::with_capacity(rect.width() as usize * rect.height() as usize);
+        for y in rect.top()..rect.bottom() {
+            for x in rect.left()..rect.right() {
+                pixels.push(canvas.get_at(x as u32, y as u32));
+            }
+        }
+        PixelBuffer {
+            width: rect.width(),
+            height: rect.height(),
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Iterates over the pixels as if the buffer was placed at the given position, skipping
+    /// the ones which would end up at negative coordinates.
+    pub fn placed_at(&self, x: i32, y: i32) -> impl Iterator<Item = (u32, u32, Color)> + '_ {
+        let width = self.width as usize;
+        self.pixels
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, &color)| {
+                let pixel_x = x + (index % width) as i32;
+                let pixel_y = y + (index / width) as i32;
+                if pixel_x >= 0 && pixel_y >= 0 {
+                    Some((pixel_x as u32, pixel_y as u32, color))
+                } else {
+                    None
+                }
+            })
+    }
+}
+
+/// Pixels which have been lifted off the active layer (or pasted) and can be moved around
+/// before being stamped back onto it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Floating {
+    pub buffer: PixelBuffer,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub rect: Rect,
+    pub floating: Option<Floating>,
+}
+
+impl Selection {
+    pub fn new(rect: Rect) -> Selection {
+        Selection {
+            rect,
+            floating: None,
+        }
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.rect.contains_point((x, y))
+    }
+
+    pub fn is_floating(&self) -> bool {
+        self.floating.is_some()
+    }
+}
@@ -0,0 +1,119 @@
+use crate::canvas::Canvas;
+use crate::layer::LayerId;
+use crate::patch::Patch;
+use crate::tile_grid::TileGrid;
+
+/// Copies of the tiles of a layer, taken right before each of them is first modified. Only the
+/// touched tiles have to be copied and compared, which keeps large images responsive.
+pub struct Snapshot {
+    layer: LayerId,
+    width: u32,
+    grid: TileGrid,
+    tiles: Vec<Option<Box<[u8]>>>,
+}
+
+impl Snapshot {
+    pub fn new(layer: LayerId, width: u32, height: u32) -> Snapshot {
+        let grid = TileGrid::new(width, height, Self::TILE_SIZE);
+        Snapshot {
+            layer,
+            width,
+            grid,
+            tiles: (0..grid.tile_count()).map(|_| None).collect(),
+        }
+    }
+
+    pub fn layer(&self) -> LayerId {
+        self.layer
+    }
+
+    /// Copies the tile containing the given pixel out of the layer data, unless it has
+    /// already been copied.
+    pub fn save_tile_at(&mut self, x: u32, y: u32, data: &[u8]) {
+        let index = self.grid.index_at(x, y);
+        if self.tiles[index].is_some() {
+            return;
+        }
+        let rect = self.grid.tile_rect(index);
+        let tile = Patch::rows(data, self.width, rect)
+            .flatten()
+            .copied()
+            .collect();
+        self.tiles[index] = Some(tile);
+    }
+
+    /// Creates patches for the copied tiles which differ from the current layer data.
+    pub fn into_patches(self, data: &[u8]) -> Vec<Patch> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, tile)| {
+                let tile = tile.as_ref()?;
+                let rect = self.grid.tile_rect(index);
+                let before = tile.chunks_exact(rect.width() as usize * Canvas::BPP);
+                let after = Patch::rows(data, self.width, rect);
+                if before.clone().eq(after.clone()) {
+                    None
+                } else {
+                    Some(Patch::between(rect, before, after))
+                }
+            })
+            .collect()
+    }
+
+    const TILE_SIZE: u32 = 64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::rect::Rect;
+
+    // Three columns and two rows of tiles, the last ones smaller than the rest.
+    const WIDTH: u32 = 130;
+    const HEIGHT: u32 = 70;
+
+    fn set(data: &mut [u8], x: u32, y: u32, value: u8) {
+        let offset = (y * WIDTH + x) as usize * Canvas::BPP;
+        data[offset..offset + Canvas::BPP].copy_from_slice(&[value; 4]);
+    }
+
+    #[test]
+    fn only_touched_tiles() {
+        let before: Vec<u8> = (0..WIDTH * HEIGHT * 4).map(|i| (i % 251) as u8).collect();
+        let mut data = before.clone();
+        let mut snapshot = Snapshot::new(LayerId(3), WIDTH, HEIGHT);
+        assert_eq!(snapshot.layer(), LayerId(3));
+        for &(x, y) in [(1, 1), (63, 63), (129, 69)].iter() {
+            snapshot.save_tile_at(x, y, &data);
+            set(&mut data, x, y, 7);
+        }
+        // Touching a tile again must not replace its copy with the modified pixels.
+        snapshot.save_tile_at(2, 2, &data);
+        set(&mut data, 2, 2, 9);
+        // A tile which is saved but left as it was yields no patch.
+        snapshot.save_tile_at(70, 10, &data);
+        assert_eq!(
+            snapshot.tiles.iter().filter(|tile| tile.is_some()).count(),
+            3
+        );
+
+        let patches = snapshot.into_patches(&data);
+        let rects: Vec<Rect> = patches.iter().map(Patch::rect).collect();
+        assert_eq!(
+            rects,
+            vec![Rect::new(0, 0, 64, 64), Rect::new(128, 64, 2, 6)]
+        );
+
+        // Undoing and redoing the patches restores the exact pixels.
+        let after = data.clone();
+        for patch in &patches {
+            patch.apply(&mut data, WIDTH);
+        }
+        assert!(data == before);
+        for patch in &patches {
+            patch.apply(&mut data, WIDTH);
+        }
+        assert!(data == after);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::geometry::Scale;
+use crate::text::Align;
+use crate::{SdlApp, SdlError};
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+
+/// What the status bar shows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    /// The image coordinate under the cursor, if the cursor is in the window.
+    pub cursor: Option<(i64, i64)>,
+    /// The color of the active layer under the cursor, if the cursor is over the canvas.
+    pub color: Option<Color>,
+    pub canvas_size: (u32, u32),
+    pub scale: Scale,
+    pub tool_name: String,
+    pub unsaved: bool,
+}
+
+/// A persistent bar along the bottom edge of the window.
+pub struct StatusBar {
+    status: Option<Status>,
+}
+
+impl StatusBar {
+    pub fn new() -> StatusBar {
+        StatusBar { status: None }
+    }
+
+    /// Replaces the shown status. Returns whether it has changed, i.e. whether the bar has to
+    /// be redrawn.
+    pub fn update(&mut self, status: Status) -> bool {
+        if self.status.as_ref() == Some(&status) {
+            return false;
+        }
+        self.status = Some(status);
+        true
+    }
+
+    pub fn draw(&mut self, sdl_app: &mut SdlApp) -> Result<(), SdlError> {
+        let status = match &self.status {
+            Some(status) => status,
+            None => return Ok(()),
+        };
+        let mut canvas = sdl_app.sdl_canvas.borrow_mut();
+        let text = &mut sdl_app.text;
+
+        let (screen_width, screen_height) = canvas.window().drawable_size();
+        let bar_rect = Self::bar_rect(screen_width, screen_height);
+        canvas.set_draw_color(Self::BACKGROUND_COLOR);
+        canvas.fill_rect(bar_rect)?;
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_line(
+            Point::new(0, bar_rect.top()),
+            Point::new(screen_width as i32, bar_rect.top()),
+        )?;
+
+        let mut left = Vec::new();
+        if let Some((x, y)) = status.cursor {
+            left.push(format!("{}, {}", x, y));
+        }
+        if let Some(color) = status.color {
+            left.push(format!(
+                "RGBA({}, {}, {}, {})  #{:02X}{:02X}{:02X}{:02X}",
+                color.r, color.g, color.b, color.a, color.r, color.g, color.b, color.a
+            ));
+        }
+        let mut right = vec![
+            format!("{}x{}", status.canvas_size.0, status.canvas_size.1),
+            status.scale.to_percentage_string(),
+            status.tool_name.clone(),
+        ];
+        if status.unsaved {
+            right.push("Unsaved".to_owned());
+        }
+
+        text.draw_in(
+            &mut canvas,
+            &left.join(Self::SEPARATOR),
+            Self::FONT_SIZE,
+            Color::BLACK,
+            bar_rect,
+            Align::Left(Self::PADDING),
+        )?;
+        text.draw_in(
+            &mut canvas,
+            &right.join(Self::SEPARATOR),
+            Self::FONT_SIZE,
+            Color::BLACK,
+            bar_rect,
+            Align::Right(Self::PADDING),
+        )?;
+        Ok(())
+    }
+
+    pub fn contains(&self, x: i32, y: i32, screen_width: u32, screen_height: u32) -> bool {
+        Self::bar_rect(screen_width, screen_height).contains_point((x, y))
+    }
+
+    fn bar_rect(screen_width: u32, screen_height: u32) -> Rect {
+        Rect::new(
+            0,
+            screen_height as i32 - Self::HEIGHT as i32,
+            screen_width.max(1),
+            Self::HEIGHT,
+        )
+    }
+
+    const HEIGHT: u32 = 24;
+    const PADDING: i32 = 8;
+    const FONT_SIZE: u16 = 14;
+    const SEPARATOR: &'static str = "   |   ";
+    const BACKGROUND_COLOR: Color = Color::RGB(230, 230, 230);
+}
@@ -0,0 +1,67 @@
+use crate::SdlCanvas;
+use std::cell::RefCell;
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+type Job = Box<dyn FnOnce(&Rc<RefCell<SdlCanvas>>) + Send>;
+
+lazy_static! {
+    static ref JOBS: Mutex<Sender<Job>> = Mutex::new(spawn_sdl_thread());
+}
+
+/// SDL can only be initialized once at a time, and a `Canvas` leaks its texture creator, which
+/// keeps SDL initialized for good. So all tests which need a canvas share one thread which
+/// owns SDL and a hidden window.
+fn spawn_sdl_thread() -> Sender<Job> {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    thread::spawn(move || {
+        // The dummy driver lets the tests run without a display.
+        env::set_var("SDL_VIDEODRIVER", "dummy");
+        let sdl_context = sdl2::init().expect("Failed to initialize SDL");
+        let video_subsystem = sdl_context.video().expect("Failed to initialize SDL video");
+        let window = video_subsystem
+            .window("OxiPaint tests", 64, 64)
+            .hidden()
+            .build()
+            .expect("Failed to create a window");
+        let sdl_canvas = Rc::new(RefCell::new(
+            window
+                .into_canvas()
+                .software()
+                .build()
+                .expect("Failed to create a renderer"),
+        ));
+        for job in receiver {
+            job(&sdl_canvas);
+        }
+    });
+    sender
+}
+
+/// Runs a test which needs an SDL canvas on the SDL thread. Panics inside the test are passed
+/// on to the caller.
+pub fn with_sdl_canvas<T: Send + 'static>(
+    test: impl FnOnce(Rc<RefCell<SdlCanvas>>) -> T + Send + 'static,
+) -> T {
+    let (result_sender, result_receiver) = mpsc::channel();
+    let job: Job = Box::new(move |sdl_canvas| {
+        let sdl_canvas = Rc::clone(sdl_canvas);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| test(sdl_canvas)));
+        let _ = result_sender.send(result);
+    });
+    JOBS.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .send(job)
+        .expect("The SDL thread has failed to start");
+    match result_receiver
+        .recv()
+        .expect("The SDL thread has failed to start")
+    {
+        Ok(value) => value,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
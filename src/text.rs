@@ -0,0 +1,186 @@
+use crate::{SdlCanvas, SdlError};
+use font_kit::family_name::FamilyName;
+use font_kit::handle::Handle;
+use font_kit::properties::Properties;
+use font_kit::source::SystemSource;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator};
+use sdl2::rwops::RWops;
+use sdl2::ttf::{Font, Sdl2TtfContext};
+use sdl2::video::WindowContext;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Where a text line is placed horizontally within a rectangle. Lines are always centered
+/// vertically.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Align {
+    /// At the left edge, with the given padding.
+    Left(i32),
+    Center,
+    /// At the right edge, with the given padding.
+    Right(i32),
+}
+
+/// Draws text with the system sans-serif font. The font is looked up once, opened once per
+/// point size, and every glyph is rendered to a texture only once per size and color.
+pub struct TextRenderer {
+    ttf_context: &'static Sdl2TtfContext,
+    texture_creator: &'static TextureCreator<WindowContext>,
+    source: FontSource,
+    fonts: HashMap<u16, Font<'static, 'static>>,
+    glyphs: HashMap<GlyphKey, Glyph>,
+}
+
+enum FontSource {
+    Path {
+        path: PathBuf,
+        index: u32,
+    },
+    /// Font data which font-kit has only in memory, e.g. from a font collection.
+    Memory {
+        bytes: &'static [u8],
+        index: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+struct GlyphKey {
+    size: u16,
+    color: (u8, u8, u8, u8),
+    ch: char,
+}
+
+struct Glyph {
+    /// Glyphs without any visible pixels, such as spaces, have no texture.
+    texture: Option<Texture<'static>>,
+    advance: i32,
+}
+
+impl TextRenderer {
+    /// The TTF context and the texture creator live as long as the application, so they are
+    /// leaked here, which lets the cached fonts and textures borrow them.
+    pub fn new(
+        ttf_context: Sdl2TtfContext,
+        texture_creator: TextureCreator<WindowContext>,
+    ) -> Result<TextRenderer, SdlError> {
+        let handle = SystemSource::new()
+            .select_best_match(&[FamilyName::SansSerif], &Properties::new())
+            .map_err(|e| e.to_string())?;
+        let source = match handle {
+            Handle::Path { path, font_index } => FontSource::Path {
+                path,
+                index: font_index,
+            },
+            Handle::Memory { bytes, font_index } => FontSource::Memory {
+                bytes: Box::leak(bytes.to_vec().into_boxed_slice()),
+                index: font_index,
+            },
+        };
+        Ok(TextRenderer {
+            ttf_context: Box::leak(Box::new(ttf_context)),
+            texture_creator: Box::leak(Box::new(texture_creator)),
+            source,
+            fonts: HashMap::new(),
+            glyphs: HashMap::new(),
+        })
+    }
+
+    fn font(&mut self, size: u16) -> Result<&Font<'static, 'static>, SdlError> {
+        if !self.fonts.contains_key(&size) {
+            let font = match &self.source {
+                FontSource::Path { path, index } => {
+                    self.ttf_context.load_font_at_index(path, *index, size)?
+                }
+                FontSource::Memory { bytes, index } => self
+                    .ttf_context
+                    .load_font_at_index_from_rwops(RWops::from_bytes(bytes)?, *index, size)?,
+            };
+            self.fonts.insert(size, font);
+        }
+        Ok(&self.fonts[&size])
+    }
+
+    fn glyph(&mut self, ch: char, size: u16, color: Color) -> Result<&Glyph, SdlError> {
+        let key = GlyphKey {
+            size,
+            color: color.rgba(),
+            ch,
+        };
+        if !self.glyphs.contains_key(&key) {
+            let texture_creator = self.texture_creator;
+            let font = self.font(size)?;
+            let advance = font
+                .find_glyph_metrics(ch)
+                .map_or(0, |metrics| metrics.advance);
+            let texture = match font.render_char(ch).blended(color) {
+                Ok(surface) => Some(
+                    texture_creator
+                        .create_texture_from_surface(surface)
+                        .map_err(|e| e.to_string())?,
+                ),
+                Err(_) => None,
+            };
+            self.glyphs.insert(key, Glyph { texture, advance });
+        }
+        Ok(&self.glyphs[&key])
+    }
+
+    /// Returns the width and the height of a line of text.
+    pub fn size_of(&mut self, text: &str, size: u16) -> Result<(u32, u32), SdlError> {
+        let height = self.font(size)?.height().max(0) as u32;
+        let mut width = 0;
+        for ch in text.chars() {
+            // The color does not matter for measuring, but measuring caches the glyphs, so
+            // black is used as the most common text color.
+            width += self.glyph(ch, size, Color::BLACK)?.advance;
+        }
+        Ok((width.max(0) as u32, height))
+    }
+
+    /// Draws a line of text with its top left corner at the given point.
+    pub fn draw(
+        &mut self,
+        canvas: &mut SdlCanvas,
+        text: &str,
+        size: u16,
+        color: Color,
+        x: i32,
+        y: i32,
+    ) -> Result<(), SdlError> {
+        let mut x = x;
+        for ch in text.chars() {
+            let glyph = self.glyph(ch, size, color)?;
+            if let Some(texture) = &glyph.texture {
+                let q = texture.query();
+                canvas.copy(texture, None, Some(Rect::new(x, y, q.width, q.height)))?;
+            }
+            x += glyph.advance;
+        }
+        Ok(())
+    }
+
+    /// Draws a line of text within a rectangle, clipping whatever does not fit.
+    pub fn draw_in(
+        &mut self,
+        canvas: &mut SdlCanvas,
+        text: &str,
+        size: u16,
+        color: Color,
+        rect: Rect,
+        align: Align,
+    ) -> Result<(), SdlError> {
+        let (width, height) = self.size_of(text, size)?;
+        let x = match align {
+            Align::Left(padding) => rect.left() + padding,
+            Align::Center => rect.center().x() - width as i32 / 2,
+            Align::Right(padding) => rect.right() - padding - width as i32,
+        };
+        let y = rect.center().y() - height as i32 / 2;
+        canvas.set_clip_rect(rect);
+        let result = self.draw(canvas, text, size, color, x, y);
+        canvas.set_clip_rect(None);
+        result
+    }
+}
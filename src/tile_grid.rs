@@ -0,0 +1,95 @@
+use sdl2::rect::Rect;
+use std::ops::Range;
+
+/// Splits an image into square tiles, numbered row by row. The tiles along the right and
+/// bottom edges may be smaller than the rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileGrid {
+    width: u32,
+    height: u32,
+    tile_size: u32,
+}
+
+impl TileGrid {
+    pub fn new(width: u32, height: u32, tile_size: u32) -> TileGrid {
+        TileGrid {
+            width,
+            height,
+            tile_size,
+        }
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.width.div_ceil(self.tile_size)
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.height.div_ceil(self.tile_size)
+    }
+
+    pub fn tile_count(&self) -> usize {
+        self.columns() as usize * self.rows() as usize
+    }
+
+    /// The index of the tile containing the given pixel.
+    pub fn index_at(&self, x: u32, y: u32) -> usize {
+        ((y / self.tile_size) * self.columns() + x / self.tile_size) as usize
+    }
+
+    pub fn tile_rect(&self, index: usize) -> Rect {
+        let index = index as u32;
+        let left = (index % self.columns()) * self.tile_size;
+        let top = (index / self.columns()) * self.tile_size;
+        Rect::new(
+            left as i32,
+            top as i32,
+            self.tile_size.min(self.width - left),
+            self.tile_size.min(self.height - top),
+        )
+    }
+
+    /// The ranges of columns and rows of the tiles which intersect the given rectangle. The
+    /// rectangle must lie within the image.
+    pub fn span(&self, rect: Rect) -> (Range<u32>, Range<u32>) {
+        let columns =
+            rect.left() as u32 / self.tile_size..(rect.right() as u32).div_ceil(self.tile_size);
+        let rows =
+            rect.top() as u32 / self.tile_size..(rect.bottom() as u32).div_ceil(self.tile_size);
+        (columns, rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles() {
+        let grid = TileGrid::new(100, 70, 32);
+        assert_eq!((grid.columns(), grid.rows(), grid.tile_count()), (4, 3, 12));
+        assert_eq!(grid.index_at(0, 0), 0);
+        assert_eq!(grid.index_at(31, 31), 0);
+        assert_eq!(grid.index_at(32, 0), 1);
+        assert_eq!(grid.index_at(0, 32), 4);
+        assert_eq!(grid.index_at(99, 69), 11);
+        assert_eq!(grid.tile_rect(0), Rect::new(0, 0, 32, 32));
+        assert_eq!(grid.tile_rect(5), Rect::new(32, 32, 32, 32));
+        assert_eq!(grid.tile_rect(3), Rect::new(96, 0, 4, 32));
+        assert_eq!(grid.tile_rect(8), Rect::new(0, 64, 32, 6));
+        assert_eq!(grid.tile_rect(11), Rect::new(96, 64, 4, 6));
+
+        let exact = TileGrid::new(64, 64, 32);
+        assert_eq!(exact.tile_count(), 4);
+        assert_eq!(exact.tile_rect(3), Rect::new(32, 32, 32, 32));
+    }
+
+    #[test]
+    fn span() {
+        let grid = TileGrid::new(100, 70, 32);
+        assert_eq!(grid.span(Rect::new(0, 0, 100, 70)), (0..4, 0..3));
+        assert_eq!(grid.span(Rect::new(0, 0, 32, 32)), (0..1, 0..1));
+        assert_eq!(grid.span(Rect::new(31, 31, 2, 2)), (0..2, 0..2));
+        assert_eq!(grid.span(Rect::new(32, 40, 1, 1)), (1..2, 1..2));
+        assert_eq!(grid.span(Rect::new(99, 69, 1, 1)), (3..4, 2..3));
+    }
+}
@@ -1,15 +1,18 @@
 use crate::draw_context::DrawContext;
 use crate::editor::Editor;
-use crate::Redraw;
+use crate::{KeyWithMod, Redraw};
 use sdl2::mouse::MouseButton;
 
 pub trait Tool {
     fn name(&self) -> String;
 
+    /// The key which selects this tool unless the user says otherwise.
+    fn default_shortcut(&self) -> KeyWithMod;
+
     fn on_mouse_button_press(
         &mut self,
         _button: MouseButton,
-        _context: &DrawContext,
+        _context: &mut DrawContext,
         _editor: &mut Editor,
     ) -> Redraw {
         Redraw::Dont
@@ -18,13 +21,18 @@ pub trait Tool {
     fn on_mouse_button_release(
         &mut self,
         _button: MouseButton,
-        _context: &DrawContext,
+        _context: &mut DrawContext,
         _editor: &mut Editor,
     ) -> Redraw {
         Redraw::Dont
     }
 
-    fn on_cursor_move(&mut self, _context: &DrawContext, _editor: &mut Editor) -> Redraw {
+    fn on_cursor_move(&mut self, _context: &mut DrawContext, _editor: &mut Editor) -> Redraw {
+        Redraw::Dont
+    }
+
+    /// Called when the keyboard modifiers (Shift, Ctrl, Alt) are pressed or released.
+    fn on_modifiers_change(&mut self, _context: &mut DrawContext, _editor: &mut Editor) -> Redraw {
         Redraw::Dont
     }
 }
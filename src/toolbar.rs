@@ -0,0 +1,75 @@
+use crate::text::Align;
+use crate::{SdlApp, SdlError};
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+
+/// A persistent vertical toolbar along the left edge of the window.
+pub struct Toolbar {
+    tool_names: Vec<String>,
+}
+
+impl Toolbar {
+    pub fn new(tool_names: Vec<String>) -> Toolbar {
+        Toolbar { tool_names }
+    }
+
+    pub fn draw(&mut self, sdl_app: &mut SdlApp, selected_tool: usize) -> Result<(), SdlError> {
+        let mut canvas = sdl_app.sdl_canvas.borrow_mut();
+        let text = &mut sdl_app.text;
+
+        let (_, screen_height) = canvas.window().drawable_size();
+        canvas.set_draw_color(Self::BACKGROUND_COLOR);
+        canvas.fill_rect(Rect::new(0, 0, Self::WIDTH, screen_height))?;
+
+        for (index, name) in self.tool_names.iter().enumerate() {
+            let button_rect = Self::button_rect(index);
+            let text_color = if index == selected_tool {
+                canvas.set_draw_color(Self::SELECTED_COLOR);
+                canvas.fill_rect(button_rect)?;
+                Color::WHITE
+            } else {
+                Color::BLACK
+            };
+
+            text.draw_in(
+                &mut canvas,
+                name,
+                Self::FONT_SIZE,
+                text_color,
+                button_rect,
+                Align::Center,
+            )?;
+        }
+
+        canvas.set_draw_color(Color::BLACK);
+        canvas.draw_line(
+            Point::new(Self::WIDTH as i32, 0),
+            Point::new(Self::WIDTH as i32, screen_height as i32),
+        )?;
+        Ok(())
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (0..Self::WIDTH as i32).contains(&x) && y >= 0
+    }
+
+    /// Returns the index of the tool whose button is located at the given point.
+    pub fn hit_test(&self, x: i32, y: i32) -> Option<usize> {
+        (0..self.tool_names.len()).find(|&index| Self::button_rect(index).contains_point((x, y)))
+    }
+
+    fn button_rect(index: usize) -> Rect {
+        Rect::new(
+            0,
+            (index as u32 * Self::BUTTON_HEIGHT) as i32,
+            Self::WIDTH,
+            Self::BUTTON_HEIGHT,
+        )
+    }
+
+    const WIDTH: u32 = 100;
+    const BUTTON_HEIGHT: u32 = 32;
+    const FONT_SIZE: u16 = 16;
+    const BACKGROUND_COLOR: Color = Color::RGB(230, 230, 230);
+    const SELECTED_COLOR: Color = Color::RGB(60, 110, 200);
+}
@@ -0,0 +1,79 @@
+use crate::draw_context::DrawContext;
+use crate::draw_primitives::*;
+use crate::editor::Editor;
+use crate::geometry::Point;
+use crate::tool::Tool;
+use crate::tools::stroke::Stroke;
+use crate::{KeyModifier, KeyWithMod, Redraw};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+
+pub struct Eraser {
+    stroke: Stroke,
+}
+
+impl Eraser {
+    pub fn new() -> Eraser {
+        Eraser {
+            stroke: Stroke::new(),
+        }
+    }
+
+    fn erase_dab(editor: &mut Editor, center: Point, size: f64) {
+        if let Some(disc) = Disc::try_new(center, size) {
+            disc.draw(&mut |x, y| {
+                editor.canvas_mut().try_set_at(x, y, Self::TRANSPARENT);
+            });
+        }
+    }
+
+    const TRANSPARENT: Color = Color::RGBA(0, 0, 0, 0);
+}
+
+impl Tool for Eraser {
+    fn name(&self) -> String {
+        "Eraser".to_owned()
+    }
+
+    fn default_shortcut(&self) -> KeyWithMod {
+        KeyModifier::new().key(Keycode::E)
+    }
+
+    fn on_mouse_button_press(
+        &mut self,
+        button: MouseButton,
+        context: &mut DrawContext,
+        editor: &mut Editor,
+    ) -> Redraw {
+        let size = context.eraser_size as f64;
+        match button {
+            MouseButton::Left => self.stroke.start(button, context, editor, |editor, point| {
+                Self::erase_dab(editor, point, size);
+            }),
+            _ => Redraw::Dont,
+        }
+    }
+
+    fn on_mouse_button_release(
+        &mut self,
+        button: MouseButton,
+        _context: &mut DrawContext,
+        editor: &mut Editor,
+    ) -> Redraw {
+        self.stroke.finish(button, editor);
+        Redraw::Dont
+    }
+
+    fn on_cursor_move(&mut self, context: &mut DrawContext, editor: &mut Editor) -> Redraw {
+        let size = context.eraser_size as f64;
+        self.stroke
+            .advance(context, editor, |editor, last_point, current_point| {
+                if let Some(line) = RoundLine::try_new(last_point, current_point, size) {
+                    line.draw(&mut |x, y| {
+                        editor.canvas_mut().try_set_at(x, y, Self::TRANSPARENT);
+                    });
+                }
+            })
+    }
+}
@@ -0,0 +1,289 @@
+use crate::canvas::Canvas;
+use crate::draw_context::{Connectivity, DrawContext};
+use crate::editor::Editor;
+use crate::tool::Tool;
+use crate::{KeyModifier, KeyWithMod, Redraw, TranslatedPoint};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+
+pub struct Fill;
+
+impl Fill {
+    pub fn new() -> Fill {
+        Fill
+    }
+}
+
+impl Tool for Fill {
+    fn name(&self) -> String {
+        "Fill".to_owned()
+    }
+
+    fn default_shortcut(&self) -> KeyWithMod {
+        KeyModifier::new().key(Keycode::B)
+    }
+
+    fn on_mouse_button_press(
+        &mut self,
+        button: MouseButton,
+        context: &mut DrawContext,
+        editor: &mut Editor,
+    ) -> Redraw {
+        match (context.color_for(button), context.cursor_position) {
+            (Some(color), TranslatedPoint::WithinCanvas(point)) => {
+                if editor.begin().is_err() {
+                    return Redraw::Dont;
+                }
+                let canvas = editor.canvas_mut();
+                let (x, y) = (point.x as u32, point.y as u32);
+                if context.fill_global {
+                    global_fill(canvas, x, y, color, context.fill_tolerance);
+                } else {
+                    flood_fill(
+                        canvas,
+                        x,
+                        y,
+                        color,
+                        context.fill_tolerance,
+                        context.fill_connectivity,
+                    );
+                }
+                editor.end();
+                Redraw::Do
+            }
+            _ => Redraw::Dont,
+        }
+    }
+}
+
+fn color_distance(a: Color, b: Color) -> u8 {
+    let channel_distance = |x: u8, y: u8| x.max(y) - x.min(y);
+    channel_distance(a.r, b.r)
+        .max(channel_distance(a.g, b.g))
+        .max(channel_distance(a.b, b.b))
+        .max(channel_distance(a.a, b.a))
+}
+
+/// Replaces every pixel of the active layer which is close enough to the color at (x, y).
+pub fn global_fill(canvas: &mut Canvas, x: u32, y: u32, color: Color, tolerance: u8) {
+    let target = match canvas.try_get_at(x, y) {
+        Some(target) => target,
+        None => return,
+    };
+    for y in 0..canvas.height() {
+        for x in 0..canvas.width() {
+            if color_distance(canvas.get_at(x, y), target) <= tolerance {
+                canvas.set_at(x, y, color);
+            }
+        }
+    }
+}
+
+/// Fills the contiguous region of the active layer around (x, y), span by span.
+///
+/// Instead of recursing into every neighbouring pixel, this keeps an explicit stack of seeds,
+/// one per horizontal run of matching pixels, so that filling the whole of a large image
+/// takes no unbounded stack space. Filled pixels are tracked in a map of one byte per canvas
+/// pixel, because with a nonzero tolerance the fill color itself may still match the target.
+pub fn flood_fill(
+    canvas: &mut Canvas,
+    x: u32,
+    y: u32,
+    color: Color,
+    tolerance: u8,
+    connectivity: Connectivity,
+) {
+    let target = match canvas.try_get_at(x, y) {
+        Some(target) => target,
+        None => return,
+    };
+    let width = canvas.width() as usize;
+    let height = canvas.height() as usize;
+    let mut visited = vec![false; width * height];
+    let matches = |canvas: &Canvas, visited: &[bool], x: usize, y: usize| {
+        !visited[y * width + x]
+            && color_distance(canvas.get_at(x as u32, y as u32), target) <= tolerance
+    };
+
+    let mut seeds = vec![(x as usize, y as usize)];
+    while let Some((x, y)) = seeds.pop() {
+        if !matches(canvas, &visited, x, y) {
+            continue;
+        }
+
+        let mut left = x;
+        while left > 0 && matches(canvas, &visited, left - 1, y) {
+            left -= 1;
+        }
+        let mut right = x;
+        while right + 1 < width && matches(canvas, &visited, right + 1, y) {
+            right += 1;
+        }
+
+        for span_x in left..=right {
+            visited[y * width + span_x] = true;
+            canvas.set_at(span_x as u32, y as u32, color);
+        }
+
+        let (scan_left, scan_right) = match connectivity {
+            Connectivity::Four => (left, right),
+            Connectivity::Eight => (left.saturating_sub(1), (right + 1).min(width - 1)),
+        };
+        let neighbour_rows = [y.checked_sub(1), Some(y + 1).filter(|&y| y < height)];
+        for &neighbour_y in neighbour_rows.iter().flatten() {
+            let mut in_run = false;
+            for scan_x in scan_left..=scan_right {
+                if matches(canvas, &visited, scan_x, neighbour_y) {
+                    if !in_run {
+                        seeds.push((scan_x, neighbour_y));
+                        in_run = true;
+                    }
+                } else {
+                    in_run = false;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::with_sdl_canvas;
+    use crate::SdlCanvas;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const RED: Color = Color::RGBA(255, 0, 0, 255);
+
+    fn gray(value: u8) -> Color {
+        Color::RGBA(value, value, value, 255)
+    }
+
+    /// Builds a canvas from rows of digits, each one the gray level of a pixel in tens.
+    fn canvas_from(sdl_canvas: Rc<RefCell<SdlCanvas>>, rows: &[&str]) -> Canvas {
+        let mut canvas = Canvas::new(rows[0].len() as u32, rows.len() as u32, sdl_canvas);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, digit) in row.bytes().enumerate() {
+                canvas.set_at(x as u32, y as u32, gray((digit - b'0') * 10));
+            }
+        }
+        canvas
+    }
+
+    /// Renders the canvas back into rows, with `*` standing for red pixels.
+    fn rows_of(canvas: &Canvas) -> Vec<String> {
+        (0..canvas.height())
+            .map(|y| {
+                (0..canvas.width())
+                    .map(|x| match canvas.get_at(x, y) {
+                        RED => '*',
+                        color => (b'0' + color.r / 10) as char,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tolerance() {
+        with_sdl_canvas(|sdl_canvas| {
+            let rows = ["0120", "0000"];
+            let mut canvas = canvas_from(Rc::clone(&sdl_canvas), &rows);
+            flood_fill(&mut canvas, 3, 0, RED, 9, Connectivity::Four);
+            assert_eq!(rows_of(&canvas), ["*12*", "****"]);
+
+            let mut canvas = canvas_from(Rc::clone(&sdl_canvas), &["0120"]);
+            flood_fill(&mut canvas, 0, 0, RED, 10, Connectivity::Four);
+            assert_eq!(rows_of(&canvas), ["**20"]);
+            let mut canvas = canvas_from(Rc::clone(&sdl_canvas), &["0120"]);
+            flood_fill(&mut canvas, 0, 0, RED, 20, Connectivity::Four);
+            assert_eq!(rows_of(&canvas), ["****"]);
+
+            let mut canvas = canvas_from(Rc::clone(&sdl_canvas), &["0123"]);
+            global_fill(&mut canvas, 1, 0, RED, 10);
+            assert_eq!(rows_of(&canvas), ["***3"]);
+            let mut canvas = canvas_from(sdl_canvas, &["0123"]);
+            global_fill(&mut canvas, 1, 0, RED, 0);
+            assert_eq!(rows_of(&canvas), ["0*23"]);
+        });
+    }
+
+    #[test]
+    fn tolerance_covers_every_channel() {
+        with_sdl_canvas(|sdl_canvas| {
+            let mut canvas = Canvas::new(3, 1, sdl_canvas);
+            canvas.set_at(1, 0, Color::RGBA(255, 255, 255, 254));
+            canvas.set_at(2, 0, Color::RGBA(255, 255, 253, 255));
+            global_fill(&mut canvas, 0, 0, RED, 1);
+            assert_eq!(canvas.get_at(0, 0), RED);
+            assert_eq!(canvas.get_at(1, 0), RED);
+            assert_eq!(canvas.get_at(2, 0), Color::RGBA(255, 255, 253, 255));
+        });
+    }
+
+    #[test]
+    fn connectivity() {
+        with_sdl_canvas(|sdl_canvas| {
+            let rows = ["09000", "90900", "09090", "00009"];
+            let mut canvas = canvas_from(Rc::clone(&sdl_canvas), &rows);
+            flood_fill(&mut canvas, 0, 0, RED, 0, Connectivity::Four);
+            assert_eq!(rows_of(&canvas), ["*9000", "90900", "09090", "00009"]);
+
+            let mut canvas = canvas_from(Rc::clone(&sdl_canvas), &rows);
+            flood_fill(&mut canvas, 1, 1, RED, 0, Connectivity::Four);
+            assert_eq!(rows_of(&canvas), ["09000", "9*900", "09090", "00009"]);
+
+            // Diagonal neighbours connect the corner to the rest of the canvas.
+            let mut canvas = canvas_from(sdl_canvas, &rows);
+            flood_fill(&mut canvas, 0, 0, RED, 0, Connectivity::Eight);
+            assert_eq!(rows_of(&canvas), ["*9***", "9*9**", "*9*9*", "****9"]);
+        });
+    }
+
+    #[test]
+    fn fill_large_canvas() {
+        with_sdl_canvas(|sdl_canvas| {
+            let (width, height) = (2000, 1500);
+            let mut canvas = Canvas::new(width, height, sdl_canvas);
+            flood_fill(
+                &mut canvas,
+                width / 2,
+                height / 2,
+                RED,
+                0,
+                Connectivity::Four,
+            );
+            for y in 0..height {
+                for x in 0..width {
+                    assert_eq!(canvas.get_at(x, y), RED);
+                }
+            }
+            global_fill(&mut canvas, 0, 0, Color::BLACK, 0);
+            for y in 0..height {
+                for x in 0..width {
+                    assert_eq!(canvas.get_at(x, y), Color::BLACK);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn fill_with_matching_color() {
+        with_sdl_canvas(|sdl_canvas| {
+            let rows = ["000", "999", "000"];
+            let mut canvas = canvas_from(Rc::clone(&sdl_canvas), &rows);
+            flood_fill(&mut canvas, 1, 0, gray(0), 0, Connectivity::Eight);
+            assert_eq!(rows_of(&canvas), rows);
+            global_fill(&mut canvas, 1, 0, gray(0), 0);
+            assert_eq!(rows_of(&canvas), rows);
+
+            // The fill color itself is within the tolerance, but the fill still stops at the
+            // edge of the region.
+            let mut canvas = canvas_from(sdl_canvas, &rows);
+            flood_fill(&mut canvas, 1, 2, gray(10), 10, Connectivity::Eight);
+            assert_eq!(rows_of(&canvas), ["000", "999", "111"]);
+        });
+    }
+}
@@ -1,7 +1,22 @@
 use crate::tool::Tool;
 
+pub mod eraser;
+pub mod fill;
 pub mod pencil;
+pub mod picker;
+pub mod select;
+pub mod shapes;
+mod stroke;
 
 pub fn list() -> Vec<Box<dyn Tool>> {
-    vec![Box::new(pencil::Pencil::new())]
+    vec![
+        Box::new(pencil::Pencil::new()),
+        Box::new(eraser::Eraser::new()),
+        Box::new(fill::Fill::new()),
+        Box::new(picker::Picker::new()),
+        Box::new(select::Select::new()),
+        Box::new(shapes::Shape::new(shapes::ShapeKind::Line)),
+        Box::new(shapes::Shape::new(shapes::ShapeKind::Rectangle)),
+        Box::new(shapes::Shape::new(shapes::ShapeKind::Ellipse)),
+    ]
 }
@@ -2,17 +2,19 @@ use crate::draw_context::DrawContext;
 use crate::draw_primitives::*;
 use crate::editor::Editor;
 use crate::tool::Tool;
-use crate::{Redraw, TranslatedPoint};
+use crate::tools::stroke::Stroke;
+use crate::{KeyModifier, KeyWithMod, Redraw};
+use sdl2::keyboard::Keycode;
 use sdl2::mouse::MouseButton;
 
 pub struct Pencil {
-    state: PencilState,
+    stroke: Stroke,
 }
 
 impl Pencil {
     pub fn new() -> Pencil {
         Pencil {
-            state: PencilState::new(),
+            stroke: Stroke::new(),
         }
     }
 }
@@ -22,120 +24,57 @@ impl Tool for Pencil {
         "Pencil".to_owned()
     }
 
+    fn default_shortcut(&self) -> KeyWithMod {
+        KeyModifier::new().key(Keycode::P)
+    }
+
     fn on_mouse_button_press(
         &mut self,
         button: MouseButton,
-        context: &DrawContext,
+        context: &mut DrawContext,
         editor: &mut Editor,
     ) -> Redraw {
-        match button {
-            MouseButton::Left => {
-                let point = context.cursor_position;
-                self.state = PencilState::Active { last_point: point };
-                editor.begin();
-                match point {
-                    TranslatedPoint::WithinCanvas(point) => {
-                        editor.canvas_mut().set_at(
-                            point.x as u32,
-                            point.y as u32,
-                            context.primary_color,
-                        );
-                        Redraw::Do
-                    }
-                    _ => Redraw::Dont,
-                }
+        let size = context.brush_size as f64;
+        let color = match context.color_for(button) {
+            Some(color) => color,
+            None => return Redraw::Dont,
+        };
+        self.stroke.start(button, context, editor, |editor, point| {
+            if let Some(dab) = Disc::try_new(point, size) {
+                dab.draw(&mut |x, y| {
+                    editor.canvas_mut().try_set_at(x, y, color);
+                });
             }
-            _ => Redraw::Dont,
-        }
+        })
     }
 
     fn on_mouse_button_release(
         &mut self,
         button: MouseButton,
-        _context: &DrawContext,
+        _context: &mut DrawContext,
         editor: &mut Editor,
     ) -> Redraw {
-        match button {
-            MouseButton::Left => {
-                self.state = PencilState::Inactive;
-                editor.end();
-            }
-            _ => (),
-        }
+        self.stroke.finish(button, editor);
         Redraw::Dont
     }
 
-    fn on_cursor_move(&mut self, context: &DrawContext, editor: &mut Editor) -> Redraw {
-        use PencilState::*;
-        use TranslatedPoint::*;
-        let state_copy = self.state;
-        match state_copy {
-            Inactive => Redraw::Dont,
-            Active {
-                last_point: OutsideWindow,
-            } => {
-                // Previous point outside the editor
-                self.state = Active {
-                    last_point: context.cursor_position,
-                };
-                Redraw::Dont
-            }
-            Active {
-                last_point: WithinCanvas(last_point),
-            }
-            | Active {
-                last_point: OutsideCanvas(last_point),
-            } => {
-                match context.cursor_position {
-                    WithinCanvas(current_point) | OutsideCanvas(current_point) => {
-                        // Previous and current points within the window
-                        let contains_last_point = editor.canvas().contains_point(last_point);
-                        if contains_last_point {
-                            editor.canvas_mut().try_set_at(
-                                last_point.x as u32,
-                                last_point.y as u32,
-                                context.primary_color,
-                            );
-                        }
-                        if editor.canvas().contains_point(current_point) {
-                            editor.canvas_mut().try_set_at(
-                                current_point.x as u32,
-                                current_point.y as u32,
-                                context.primary_color,
-                            );
-                        }
-
-                        if let Some(line) = HardLine::try_new(last_point, current_point, 1.0) {
-                            line.draw(&mut |x, y| {
-                                editor.canvas_mut().try_set_at(x, y, context.primary_color);
-                            });
-                        }
-                        self.state = Active {
-                            last_point: WithinCanvas(current_point),
-                        };
-                        Redraw::Do
-                    }
-                    OutsideWindow => {
-                        // Previous point within, but current point outside the window
-                        self.state = Active {
-                            last_point: OutsideWindow,
-                        };
-                        Redraw::Dont
-                    }
+    fn on_cursor_move(&mut self, context: &mut DrawContext, editor: &mut Editor) -> Redraw {
+        let size = context.brush_size as f64;
+        let color = match self
+            .stroke
+            .button()
+            .and_then(|button| context.color_for(button))
+        {
+            Some(color) => color,
+            None => return Redraw::Dont,
+        };
+        self.stroke
+            .advance(context, editor, |editor, last_point, current_point| {
+                if let Some(line) = RoundLine::try_new(last_point, current_point, size) {
+                    line.draw(&mut |x, y| {
+                        editor.canvas_mut().try_set_at(x, y, color);
+                    });
                 }
-            }
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum PencilState {
-    Inactive,
-    Active { last_point: TranslatedPoint },
-}
-
-impl PencilState {
-    fn new() -> PencilState {
-        PencilState::Inactive
+            })
     }
 }
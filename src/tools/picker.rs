@@ -0,0 +1,109 @@
+use crate::canvas::Canvas;
+use crate::draw_context::DrawContext;
+use crate::editor::Editor;
+use crate::geometry::Point;
+use crate::tool::Tool;
+use crate::{KeyModifier, KeyWithMod, Redraw, TranslatedPoint};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+
+pub struct Picker {
+    held_button: Option<MouseButton>,
+}
+
+impl Picker {
+    pub fn new() -> Picker {
+        Picker { held_button: None }
+    }
+
+    fn pick(context: &mut DrawContext, editor: &Editor, button: MouseButton) -> Redraw {
+        let point = match context.cursor_position {
+            TranslatedPoint::WithinCanvas(point) => point,
+            _ => return Redraw::Dont,
+        };
+        let color = sample(editor.canvas(), point, context.picker_size);
+        if let Some(target) = context.color_for_mut(button) {
+            *target = color;
+            println!(
+                "Picked color: #{:02x}{:02x}{:02x}{:02x}",
+                color.r, color.g, color.b, color.a
+            );
+        }
+        Redraw::Dont
+    }
+}
+
+impl Tool for Picker {
+    fn name(&self) -> String {
+        "Picker".to_owned()
+    }
+
+    fn default_shortcut(&self) -> KeyWithMod {
+        KeyModifier::new().key(Keycode::I)
+    }
+
+    fn on_mouse_button_press(
+        &mut self,
+        button: MouseButton,
+        context: &mut DrawContext,
+        editor: &mut Editor,
+    ) -> Redraw {
+        if self.held_button.is_some() || context.color_for(button).is_none() {
+            return Redraw::Dont;
+        }
+        self.held_button = Some(button);
+        Self::pick(context, editor, button)
+    }
+
+    fn on_mouse_button_release(
+        &mut self,
+        button: MouseButton,
+        _context: &mut DrawContext,
+        _editor: &mut Editor,
+    ) -> Redraw {
+        if self.held_button == Some(button) {
+            self.held_button = None;
+        }
+        Redraw::Dont
+    }
+
+    fn on_cursor_move(&mut self, context: &mut DrawContext, editor: &mut Editor) -> Redraw {
+        match self.held_button {
+            Some(button) => Self::pick(context, editor, button),
+            None => Redraw::Dont,
+        }
+    }
+}
+
+/// Averages the colors of the active layer in a `size`×`size` square around the point.
+/// Color channels are weighted by alpha, so that transparent pixels do not darken the result.
+fn sample(canvas: &Canvas, point: Point, size: u32) -> Color {
+    let radius = (size / 2) as i64;
+    let (center_x, center_y) = (point.x as i64, point.y as i64);
+    let mut count = 0u64;
+    let (mut r, mut g, mut b, mut a) = (0u64, 0u64, 0u64, 0u64);
+    for y in center_y - radius..=center_y + radius {
+        for x in center_x - radius..=center_x + radius {
+            if x < 0 || y < 0 {
+                continue;
+            }
+            if let Some(color) = canvas.try_get_at(x as u32, y as u32) {
+                let alpha = color.a as u64;
+                r += color.r as u64 * alpha;
+                g += color.g as u64 * alpha;
+                b += color.b as u64 * alpha;
+                a += alpha;
+                count += 1;
+            }
+        }
+    }
+
+    let average = |sum: u64| sum.checked_div(a).unwrap_or(0) as u8;
+    Color::RGBA(
+        average(r),
+        average(g),
+        average(b),
+        a.checked_div(count).unwrap_or(0) as u8,
+    )
+}
@@ -0,0 +1,123 @@
+use crate::draw_context::DrawContext;
+use crate::editor::Editor;
+use crate::tool::Tool;
+use crate::{KeyModifier, KeyWithMod, Redraw};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::rect::Rect;
+
+pub struct Select {
+    state: SelectState,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum SelectState {
+    Idle,
+    Selecting { start: (i32, i32) },
+    Moving { grab_offset: (i32, i32) },
+}
+
+impl Select {
+    pub fn new() -> Select {
+        Select {
+            state: SelectState::Idle,
+        }
+    }
+}
+
+fn cursor_pixel(context: &DrawContext) -> Option<(i32, i32)> {
+    context
+        .cursor_position
+        .point()
+        .map(|point| (point.x.floor() as i32, point.y.floor() as i32))
+}
+
+fn rect_between(a: (i32, i32), b: (i32, i32)) -> Rect {
+    let left = a.0.min(b.0);
+    let top = a.1.min(b.1);
+    let right = a.0.max(b.0);
+    let bottom = a.1.max(b.1);
+    Rect::new(
+        left,
+        top,
+        (right - left + 1) as u32,
+        (bottom - top + 1) as u32,
+    )
+}
+
+impl Tool for Select {
+    fn name(&self) -> String {
+        "Select".to_owned()
+    }
+
+    fn default_shortcut(&self) -> KeyWithMod {
+        KeyModifier::new().key(Keycode::M)
+    }
+
+    fn on_mouse_button_press(
+        &mut self,
+        button: MouseButton,
+        context: &mut DrawContext,
+        editor: &mut Editor,
+    ) -> Redraw {
+        let pixel = match (button, self.state, cursor_pixel(context)) {
+            (MouseButton::Left, SelectState::Idle, Some(pixel)) => pixel,
+            _ => return Redraw::Dont,
+        };
+
+        match editor.selection() {
+            Some(selection) if selection.contains(pixel.0, pixel.1) => {
+                if editor.lift_selection().is_err() {
+                    return Redraw::Dont;
+                }
+                let rect = editor.selection().unwrap().rect;
+                self.state = SelectState::Moving {
+                    grab_offset: (pixel.0 - rect.x(), pixel.1 - rect.y()),
+                };
+            }
+            _ => {
+                editor.select(None);
+                self.state = SelectState::Selecting { start: pixel };
+            }
+        }
+        Redraw::Do
+    }
+
+    fn on_mouse_button_release(
+        &mut self,
+        button: MouseButton,
+        context: &mut DrawContext,
+        editor: &mut Editor,
+    ) -> Redraw {
+        if button != MouseButton::Left {
+            return Redraw::Dont;
+        }
+        if let SelectState::Selecting { start } = self.state {
+            // A simple click without dragging deselects everything.
+            let end = cursor_pixel(context);
+            if end.is_none() || end == Some(start) {
+                editor.select(None);
+            }
+        }
+        self.state = SelectState::Idle;
+        Redraw::Do
+    }
+
+    fn on_cursor_move(&mut self, context: &mut DrawContext, editor: &mut Editor) -> Redraw {
+        let pixel = match cursor_pixel(context) {
+            Some(pixel) => pixel,
+            None => return Redraw::Dont,
+        };
+        match self.state {
+            SelectState::Idle => Redraw::Dont,
+            SelectState::Selecting { start } => {
+                editor.select(Some(rect_between(start, pixel)));
+                Redraw::Do
+            }
+            SelectState::Moving { grab_offset } => {
+                editor.move_floating_selection(pixel.0 - grab_offset.0, pixel.1 - grab_offset.1);
+                Redraw::Do
+            }
+        }
+    }
+}
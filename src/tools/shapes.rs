@@ -0,0 +1,208 @@
+use crate::draw_context::DrawContext;
+use crate::draw_primitives::*;
+use crate::editor::Editor;
+use crate::geometry::Point;
+use crate::tool::Tool;
+use crate::{KeyModifier, KeyWithMod, Redraw};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use std::f64::consts::FRAC_PI_4;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ShapeKind {
+    Line,
+    Rectangle,
+    Ellipse,
+}
+
+/// A tool which draws a shape by dragging between two points. While the mouse button is held,
+/// the shape is only shown as a canvas preview; it is committed on release.
+pub struct Shape {
+    kind: ShapeKind,
+    drag: Option<Drag>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Drag {
+    button: MouseButton,
+    color: Color,
+    start: Point,
+    end: Point,
+}
+
+impl Shape {
+    pub fn new(kind: ShapeKind) -> Shape {
+        Shape { kind, drag: None }
+    }
+
+    /// Draws the shape as horizontal runs of pixels, clipped to a canvas of the given size.
+    /// `put_span(y, left, right)` covers the pixels from `left` up to, but not including,
+    /// `right`.
+    fn draw_spans(
+        &self,
+        drag: &Drag,
+        context: &DrawContext,
+        width: u32,
+        height: u32,
+        put_span: &mut impl FnMut(u32, u32, u32),
+    ) {
+        let constrained = context.keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+        let thickness = context.brush_size;
+        let outline = if context.shape_filled {
+            None
+        } else {
+            Some(thickness)
+        };
+        match self.kind {
+            ShapeKind::Line => {
+                let end = if constrained {
+                    snap_to_45_degrees(drag.start, drag.end)
+                } else {
+                    drag.end
+                };
+                if let Some(line) = RoundLine::try_new(drag.start, end, thickness as f64) {
+                    line.draw(&mut |x, y| {
+                        if x < width && y < height {
+                            put_span(y, x, x + 1);
+                        }
+                    });
+                }
+            }
+            ShapeKind::Rectangle | ShapeKind::Ellipse => {
+                let start = to_pixel(drag.start);
+                let mut end = to_pixel(drag.end);
+                if constrained {
+                    end = make_square(start, end);
+                }
+                if self.kind == ShapeKind::Rectangle {
+                    PixelRect::new(start, end, outline).draw_spans(width, height, put_span);
+                } else {
+                    Ellipse::new(start, end, outline).draw_spans(width, height, put_span);
+                }
+            }
+        }
+    }
+
+    fn update_preview(&self, context: &DrawContext, editor: &mut Editor) -> Redraw {
+        match &self.drag {
+            Some(drag) => {
+                let canvas = editor.canvas_mut();
+                let (width, height) = (canvas.width(), canvas.height());
+                let mut spans = Vec::new();
+                self.draw_spans(drag, context, width, height, &mut |y, left, right| {
+                    spans.push((y, left, right))
+                });
+                canvas.set_preview_spans(drag.color, spans);
+                Redraw::Do
+            }
+            None => Redraw::Dont,
+        }
+    }
+}
+
+fn to_pixel(point: Point) -> (i64, i64) {
+    (point.x.floor() as i64, point.y.floor() as i64)
+}
+
+fn make_square(start: (i64, i64), end: (i64, i64)) -> (i64, i64) {
+    let dx = end.0 - start.0;
+    let dy = end.1 - start.1;
+    let side = dx.abs().max(dy.abs());
+    let sign = |d: i64| if d < 0 { -1 } else { 1 };
+    (start.0 + side * sign(dx), start.1 + side * sign(dy))
+}
+
+fn snap_to_45_degrees(start: Point, end: Point) -> Point {
+    let dx = end.x - start.x;
+    let dy = end.y - start.y;
+    let angle = (dy.atan2(dx) / FRAC_PI_4).round() * FRAC_PI_4;
+    let length = dx.hypot(dy);
+    Point::new(
+        start.x + length * angle.cos(),
+        start.y + length * angle.sin(),
+    )
+}
+
+impl Tool for Shape {
+    fn name(&self) -> String {
+        match self.kind {
+            ShapeKind::Line => "Line",
+            ShapeKind::Rectangle => "Rectangle",
+            ShapeKind::Ellipse => "Ellipse",
+        }
+        .to_owned()
+    }
+
+    fn default_shortcut(&self) -> KeyWithMod {
+        let key = match self.kind {
+            ShapeKind::Line => Keycode::L,
+            ShapeKind::Rectangle => Keycode::R,
+            ShapeKind::Ellipse => Keycode::O,
+        };
+        KeyModifier::new().key(key)
+    }
+
+    fn on_mouse_button_press(
+        &mut self,
+        button: MouseButton,
+        context: &mut DrawContext,
+        editor: &mut Editor,
+    ) -> Redraw {
+        if self.drag.is_some() {
+            return Redraw::Dont;
+        }
+        match (context.color_for(button), context.cursor_position.point()) {
+            (Some(color), Some(point)) => {
+                if editor.begin().is_err() {
+                    return Redraw::Dont;
+                }
+                self.drag = Some(Drag {
+                    button,
+                    color,
+                    start: point,
+                    end: point,
+                });
+                self.update_preview(context, editor)
+            }
+            _ => Redraw::Dont,
+        }
+    }
+
+    fn on_mouse_button_release(
+        &mut self,
+        button: MouseButton,
+        context: &mut DrawContext,
+        editor: &mut Editor,
+    ) -> Redraw {
+        let drag = match self.drag {
+            Some(drag) if drag.button == button => drag,
+            _ => return Redraw::Dont,
+        };
+        self.drag = None;
+        let canvas = editor.canvas_mut();
+        canvas.clear_preview();
+        let (width, height) = (canvas.width(), canvas.height());
+        self.draw_spans(&drag, context, width, height, &mut |y, left, right| {
+            for x in left..right {
+                canvas.try_set_at(x, y, drag.color);
+            }
+        });
+        editor.end();
+        Redraw::Do
+    }
+
+    fn on_cursor_move(&mut self, context: &mut DrawContext, editor: &mut Editor) -> Redraw {
+        match (&mut self.drag, context.cursor_position.point()) {
+            (Some(drag), Some(point)) => {
+                drag.end = point;
+                self.update_preview(context, editor)
+            }
+            _ => Redraw::Dont,
+        }
+    }
+
+    fn on_modifiers_change(&mut self, context: &mut DrawContext, editor: &mut Editor) -> Redraw {
+        self.update_preview(context, editor)
+    }
+}
@@ -0,0 +1,122 @@
+use crate::draw_context::DrawContext;
+use crate::editor::Editor;
+use crate::geometry::Point;
+use crate::{Redraw, TranslatedPoint};
+use sdl2::mouse::MouseButton;
+
+/// Tracks the cursor during a freehand stroke, so that tools like `Pencil` and `Eraser` only
+/// have to describe how to paint a single dab and a single segment of the stroke.
+pub struct Stroke {
+    state: StrokeState,
+}
+
+impl Stroke {
+    pub fn new() -> Stroke {
+        Stroke {
+            state: StrokeState::Inactive,
+        }
+    }
+
+    /// The mouse button which started the current stroke, if there is one.
+    pub fn button(&self) -> Option<MouseButton> {
+        match self.state {
+            StrokeState::Inactive => None,
+            StrokeState::Active { button, .. } => Some(button),
+        }
+    }
+
+    /// Starts a new stroke, unless one is already in progress.
+    pub fn start(
+        &mut self,
+        button: MouseButton,
+        context: &DrawContext,
+        editor: &mut Editor,
+        dab: impl FnOnce(&mut Editor, Point),
+    ) -> Redraw {
+        if self.button().is_some() || editor.begin().is_err() {
+            return Redraw::Dont;
+        }
+        let point = context.cursor_position;
+        self.state = StrokeState::Active {
+            last_point: point,
+            button,
+        };
+        match point {
+            TranslatedPoint::WithinCanvas(point) => {
+                dab(editor, point);
+                Redraw::Do
+            }
+            _ => Redraw::Dont,
+        }
+    }
+
+    /// Finishes the current stroke if it was started by the given button.
+    pub fn finish(&mut self, button: MouseButton, editor: &mut Editor) {
+        if self.button() == Some(button) {
+            self.state = StrokeState::Inactive;
+            editor.end();
+        }
+    }
+
+    pub fn advance(
+        &mut self,
+        context: &DrawContext,
+        editor: &mut Editor,
+        segment: impl FnOnce(&mut Editor, Point, Point),
+    ) -> Redraw {
+        use StrokeState::*;
+        use TranslatedPoint::*;
+        let state_copy = self.state;
+        match state_copy {
+            Inactive => Redraw::Dont,
+            Active {
+                last_point: OutsideWindow,
+                button,
+            } => {
+                // Previous point outside the editor
+                self.state = Active {
+                    last_point: context.cursor_position,
+                    button,
+                };
+                Redraw::Dont
+            }
+            Active {
+                last_point: WithinCanvas(last_point),
+                button,
+            }
+            | Active {
+                last_point: OutsideCanvas(last_point),
+                button,
+            } => {
+                match context.cursor_position {
+                    WithinCanvas(current_point) | OutsideCanvas(current_point) => {
+                        // Previous and current points within the window
+                        segment(editor, last_point, current_point);
+                        self.state = Active {
+                            last_point: WithinCanvas(current_point),
+                            button,
+                        };
+                        Redraw::Do
+                    }
+                    OutsideWindow => {
+                        // Previous point within, but current point outside the window
+                        self.state = Active {
+                            last_point: OutsideWindow,
+                            button,
+                        };
+                        Redraw::Dont
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StrokeState {
+    Inactive,
+    Active {
+        last_point: TranslatedPoint,
+        button: MouseButton,
+    },
+}